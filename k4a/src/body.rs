@@ -4,4 +4,8 @@ pub type Joint = libk4a_sys::k4abt_joint_t;
 pub type Float3 = libk4a_sys::k4a_float3_t;
 pub type Float2 = libk4a_sys::k4a_float2_t;
 pub type Quaternion = libk4a_sys::k4a_quaternion_t;
+pub use libk4a_sys::k4abt_joint_confidence_level_t as JointConfidenceLevel;
 pub use libk4a_sys::k4abt_joint_id_t as joint_id;
+
+/// Value of a body index map pixel that does not belong to any body.
+pub const BODY_INDEX_MAP_BACKGROUND: u8 = libk4a_sys::K4ABT_BODY_INDEX_MAP_BACKGROUND as u8;
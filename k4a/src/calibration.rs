@@ -1 +1,163 @@
-pub type Calibration = libk4a_sys::k4a_calibration_t;
+use std::ops::Deref;
+
+use super::body::{Float2, Float3};
+use super::error::{k4a_result, Error};
+use super::image::Image;
+
+pub type CalibrationType = libk4a_sys::k4a_calibration_type_t;
+
+#[derive(Debug)]
+pub struct Calibration {
+    calibration: libk4a_sys::k4a_calibration_t,
+}
+
+impl Calibration {
+    pub fn from_raw(calibration: libk4a_sys::k4a_calibration_t) -> Self {
+        Self { calibration }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const libk4a_sys::k4a_calibration_t {
+        &self.calibration
+    }
+
+    /// Transforms a point in millimeters between the coordinate systems of two sensors.
+    pub fn convert_3d_to_3d(
+        &self,
+        source_point3d_mm: &Float3,
+        source_camera: CalibrationType,
+        target_camera: CalibrationType,
+    ) -> Result<Float3, Error> {
+        let mut target = Float3 { x: 0.0, y: 0.0, z: 0.0 };
+        let result = unsafe {
+            libk4a_sys::k4a_calibration_3d_to_3d(
+                self.as_ptr(),
+                source_point3d_mm,
+                source_camera,
+                target_camera,
+                &mut target,
+            )
+        };
+        k4a_result(result, "k4a_calibration_3d_to_3d", || {
+            format!("{:?}, {:?} -> {:?}", source_point3d_mm, source_camera, target_camera)
+        })?;
+        Ok(target)
+    }
+
+    /// Unprojects a pixel with known depth. Returns `None` if the pixel is outside the
+    /// valid area of the source camera.
+    pub fn convert_2d_to_3d(
+        &self,
+        source_point2d: &Float2,
+        source_depth_mm: f32,
+        source_camera: CalibrationType,
+        target_camera: CalibrationType,
+    ) -> Result<Option<Float3>, Error> {
+        let mut target = Float3 { x: 0.0, y: 0.0, z: 0.0 };
+        let mut valid = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_calibration_2d_to_3d(
+                self.as_ptr(),
+                source_point2d,
+                source_depth_mm,
+                source_camera,
+                target_camera,
+                &mut target,
+                &mut valid,
+            )
+        };
+        k4a_result(result, "k4a_calibration_2d_to_3d", || {
+            format!(
+                "{:?}, depth: {}, {:?} -> {:?}",
+                source_point2d, source_depth_mm, source_camera, target_camera
+            )
+        })?;
+        Ok(if valid != 0 { Some(target) } else { None })
+    }
+
+    /// Projects a point in millimeters onto the image of `target_camera`. Returns `None`
+    /// if it falls outside the valid area of the camera.
+    pub fn convert_3d_to_2d(
+        &self,
+        source_point3d_mm: &Float3,
+        source_camera: CalibrationType,
+        target_camera: CalibrationType,
+    ) -> Result<Option<Float2>, Error> {
+        let mut target = Float2 { x: 0.0, y: 0.0 };
+        let mut valid = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_calibration_3d_to_2d(
+                self.as_ptr(),
+                source_point3d_mm,
+                source_camera,
+                target_camera,
+                &mut target,
+                &mut valid,
+            )
+        };
+        k4a_result(result, "k4a_calibration_3d_to_2d", || {
+            format!("{:?}, {:?} -> {:?}", source_point3d_mm, source_camera, target_camera)
+        })?;
+        Ok(if valid != 0 { Some(target) } else { None })
+    }
+
+    pub fn convert_2d_to_2d(
+        &self,
+        source_point2d: &Float2,
+        source_depth_mm: f32,
+        source_camera: CalibrationType,
+        target_camera: CalibrationType,
+    ) -> Result<Option<Float2>, Error> {
+        let mut target = Float2 { x: 0.0, y: 0.0 };
+        let mut valid = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_calibration_2d_to_2d(
+                self.as_ptr(),
+                source_point2d,
+                source_depth_mm,
+                source_camera,
+                target_camera,
+                &mut target,
+                &mut valid,
+            )
+        };
+        k4a_result(result, "k4a_calibration_2d_to_2d", || {
+            format!(
+                "{:?}, depth: {}, {:?} -> {:?}",
+                source_point2d, source_depth_mm, source_camera, target_camera
+            )
+        })?;
+        Ok(if valid != 0 { Some(target) } else { None })
+    }
+
+    /// Finds the depth pixel matching a color pixel by searching along its epipolar line
+    /// in `depth_image`.
+    pub fn convert_color_2d_to_depth_2d(
+        &self,
+        source_point2d: &Float2,
+        depth_image: &Image,
+    ) -> Result<Option<Float2>, Error> {
+        let mut target = Float2 { x: 0.0, y: 0.0 };
+        let mut valid = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_calibration_color_2d_to_depth_2d(
+                self.as_ptr(),
+                source_point2d,
+                **depth_image,
+                &mut target,
+                &mut valid,
+            )
+        };
+        k4a_result(result, "k4a_calibration_color_2d_to_depth_2d", || {
+            format!("{:?}", source_point2d)
+        })?;
+        Ok(if valid != 0 { Some(target) } else { None })
+    }
+}
+
+impl Deref for Calibration {
+    type Target = libk4a_sys::k4a_calibration_t;
+
+    fn deref(&self) -> &Self::Target {
+        &self.calibration
+    }
+}
@@ -1,31 +1,94 @@
-use std::{marker::PhantomData, ops::Deref};
+use std::fmt;
+use std::ops::Deref;
 
-#[derive(Debug)]
-pub struct Capture<'d> {
+use super::image::Image;
+use super::owner::Owner;
+
+/// A reference counted capture. It keeps the device or playback it came from open.
+pub struct Capture {
     capture_handle: libk4a_sys::k4a_capture_t,
-    _phantom: PhantomData<&'d ()>,
+    owner: Owner,
 }
 
-impl<'d> Capture<'d> {
-    #[allow(clippy::missing_safety_doc)]
+// A capture is complete by the time the SDK hands it out: the methods here only take
+// new references to its images, and `k4a_capture_reference` and `k4a_capture_release`
+// count atomically, so clones may be released on any thread.
+unsafe impl Send for Capture {}
+unsafe impl Sync for Capture {}
+
+impl Capture {
+    /// # Safety
+    ///
+    /// Ensure `capture_handle` is a valid capture whose reference is handed over, and
+    /// that whatever produced it outlives the returned `Capture`.
     pub unsafe fn from_handle(capture_handle: libk4a_sys::k4a_capture_t) -> Self {
+        Self::from_owned_handle(capture_handle, None)
+    }
+
+    pub(crate) unsafe fn from_owned_handle(capture_handle: libk4a_sys::k4a_capture_t, owner: Owner) -> Self {
         Self {
             capture_handle,
-            _phantom: PhantomData,
+            owner,
+        }
+    }
+
+    /// The device or playback the capture was read from.
+    pub(crate) fn owner(&self) -> &Owner {
+        &self.owner
+    }
+
+    pub fn depth(&self) -> Option<Image> {
+        unsafe {
+            Image::from_nullable_handle(
+                libk4a_sys::k4a_capture_get_depth_image(self.capture_handle),
+                self.owner.clone(),
+            )
         }
     }
+
+    pub fn ir(&self) -> Option<Image> {
+        unsafe {
+            Image::from_nullable_handle(
+                libk4a_sys::k4a_capture_get_ir_image(self.capture_handle),
+                self.owner.clone(),
+            )
+        }
+    }
+
+    pub fn color(&self) -> Option<Image> {
+        unsafe {
+            Image::from_nullable_handle(
+                libk4a_sys::k4a_capture_get_color_image(self.capture_handle),
+                self.owner.clone(),
+            )
+        }
+    }
+
+    /// Device timestamp of the first image found, in depth, IR, color order.
+    pub fn get_device_timestamp_usec(&self) -> Option<u64> {
+        self.depth()
+            .or_else(|| self.ir())
+            .or_else(|| self.color())
+            .map(|image| image.get_device_timestamp_usec())
+    }
 }
 
-impl<'d> Clone for Capture<'d> {
+impl Clone for Capture {
     fn clone(&self) -> Self {
         unsafe {
             libk4a_sys::k4a_capture_reference(self.capture_handle);
-            Self::from_handle(self.capture_handle)
+            Self::from_owned_handle(self.capture_handle, self.owner.clone())
         }
     }
 }
 
-impl<'d> Drop for Capture<'d> {
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Capture").field("capture_handle", &self.capture_handle).finish()
+    }
+}
+
+impl Drop for Capture {
     fn drop(&mut self) {
         let capture_handle = self.capture_handle;
         if capture_handle.is_null() {
@@ -38,7 +101,7 @@ impl<'d> Drop for Capture<'d> {
     }
 }
 
-impl<'d> Deref for Capture<'d> {
+impl Deref for Capture {
     type Target = libk4a_sys::k4a_capture_t;
 
     fn deref(&self) -> &Self::Target {
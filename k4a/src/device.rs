@@ -1,20 +1,187 @@
+use std::ffi::CStr;
 use std::ops::Deref;
+use std::sync::Arc;
 
 use super::calibration::Calibration;
 use super::capture::Capture;
 use super::device_configuration::{ColorResolution, DepthMode, DeviceConfiguration};
-use super::error::{k4a_result, k4a_wait_result, Error, WaitError};
+use super::error::{k4a_result, k4a_wait_result, Error, OpenError, WaitError};
+use super::imu::ImuSample;
+
+pub type HardwareVersion = libk4a_sys::k4a_hardware_version_t;
+pub type Version = libk4a_sys::k4a_version_t;
+pub type ColorControlCommand = libk4a_sys::k4a_color_control_command_t;
+pub type ColorControlMode = libk4a_sys::k4a_color_control_mode_t;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColorControlCapabilities {
+    pub supports_auto: bool,
+    pub min_value: i32,
+    pub max_value: i32,
+    pub step_value: i32,
+    pub default_value: i32,
+    pub default_mode: ColorControlMode,
+}
+
+/// A connected device, as listed by `Device::enumerate`.
+#[derive(Debug)]
+pub struct DeviceInfo {
+    pub index: u32,
+    pub serial_number: String,
+    pub version: HardwareVersion,
+}
+
+// Shared by a `Device`, the `RunningDevice` it becomes and the captures read from it.
+pub(crate) struct DeviceHandle(libk4a_sys::k4a_device_t);
+
+// The Sensor SDK locks around each device call, so captures, IMU samples and color
+// controls can be read from different threads at once. The handle is closed exactly
+// once, when the last `Arc` is dropped.
+unsafe impl Send for DeviceHandle {}
+unsafe impl Sync for DeviceHandle {}
+
+impl Drop for DeviceHandle {
+    fn drop(&mut self) {
+        unsafe { libk4a_sys::k4a_device_close(self.0) };
+    }
+}
 
 pub struct Device {
-    device_handle: libk4a_sys::k4a_device_t,
+    device_handle: Arc<DeviceHandle>,
 }
 
 impl Device {
     pub fn open(index: u32) -> Result<Self, Error> {
         let mut device_handle = std::ptr::null_mut();
         let result = unsafe { libk4a_sys::k4a_device_open(index, &mut device_handle) };
-        k4a_result(result)?;
-        Ok(Device { device_handle })
+        k4a_result(result, "k4a_device_open", || format!("index: {}", index))?;
+        Ok(Device {
+            device_handle: Arc::new(DeviceHandle(device_handle)),
+        })
+    }
+
+    /// Opens the device with this serial number. Indices change as devices are plugged
+    /// in and out, serial numbers do not.
+    pub fn open_by_serial(serial_number: &str) -> Result<Self, OpenError> {
+        for index in 0..Device::get_installed_count() {
+            // devices already open, here or in another process, cannot be identified, and
+            // neither can those whose serial number cannot be read
+            if let Ok(device) = Device::open(index) {
+                if device.get_serialnum().is_ok_and(|serial| serial == serial_number) {
+                    return Ok(device);
+                }
+            }
+        }
+        Err(OpenError::NotFound(serial_number.to_string()))
+    }
+
+    /// Opens every connected device in turn. Devices that are already open fail.
+    pub fn enumerate() -> Vec<Result<DeviceInfo, Error>> {
+        (0..Device::get_installed_count())
+            .map(|index| {
+                let device = Device::open(index)?;
+                Ok(DeviceInfo {
+                    index,
+                    serial_number: device.get_serialnum()?,
+                    version: device.get_version()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The number of devices connected, which can be opened by index `0..count`.
+    pub fn get_installed_count() -> u32 {
+        unsafe { libk4a_sys::k4a_device_get_installed_count() }
+    }
+
+    /// Firmware versions.
+    pub fn get_version(&self) -> Result<HardwareVersion, Error> {
+        let mut version = std::mem::MaybeUninit::uninit();
+        let result = unsafe { libk4a_sys::k4a_device_get_version(self.device_handle.0, version.as_mut_ptr()) };
+        k4a_result(result, "k4a_device_get_version", String::new)?;
+        Ok(unsafe { version.assume_init() })
+    }
+
+    pub fn get_serialnum(&self) -> Result<String, Error> {
+        let mut size = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_device_get_serialnum(self.device_handle.0, std::ptr::null_mut(), &mut size)
+        };
+        if result != libk4a_sys::k4a_buffer_result_t::K4A_BUFFER_RESULT_TOO_SMALL {
+            return Err(Error::new("k4a_device_get_serialnum", String::new()));
+        }
+        let mut serial_number = vec![0u8; size];
+        let result = unsafe {
+            libk4a_sys::k4a_device_get_serialnum(
+                self.device_handle.0,
+                serial_number.as_mut_ptr() as *mut _,
+                &mut size,
+            )
+        };
+        if result != libk4a_sys::k4a_buffer_result_t::K4A_BUFFER_RESULT_SUCCEEDED {
+            return Err(Error::new("k4a_device_get_serialnum", format!("size: {}", size)));
+        }
+        let serial_number = CStr::from_bytes_until_nul(&serial_number)
+            .map(|serial_number| serial_number.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(serial_number)
+    }
+
+    pub fn get_color_control_capabilities(
+        &self,
+        command: ColorControlCommand,
+    ) -> Result<ColorControlCapabilities, Error> {
+        let mut supports_auto = false;
+        let (mut min_value, mut max_value, mut step_value, mut default_value) = (0, 0, 0, 0);
+        let mut default_mode = ColorControlMode::K4A_COLOR_CONTROL_MODE_AUTO;
+        let result = unsafe {
+            libk4a_sys::k4a_device_get_color_control_capabilities(
+                self.device_handle.0,
+                command,
+                &mut supports_auto,
+                &mut min_value,
+                &mut max_value,
+                &mut step_value,
+                &mut default_value,
+                &mut default_mode,
+            )
+        };
+        k4a_result(result, "k4a_device_get_color_control_capabilities", || format!("{:?}", command))?;
+        Ok(ColorControlCapabilities {
+            supports_auto,
+            min_value,
+            max_value,
+            step_value,
+            default_value,
+            default_mode,
+        })
+    }
+
+    /// The value is only meaningful in `K4A_COLOR_CONTROL_MODE_MANUAL`.
+    pub fn get_color_control(&self, command: ColorControlCommand) -> Result<(ColorControlMode, i32), Error> {
+        let mut mode = ColorControlMode::K4A_COLOR_CONTROL_MODE_AUTO;
+        let mut value = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_device_get_color_control(self.device_handle.0, command, &mut mode, &mut value)
+        };
+        k4a_result(result, "k4a_device_get_color_control", || format!("{:?}", command))?;
+        Ok((mode, value))
+    }
+
+    /// May be called before the cameras are started. `value` is ignored in
+    /// `K4A_COLOR_CONTROL_MODE_AUTO`.
+    pub fn set_color_control(
+        &self,
+        command: ColorControlCommand,
+        mode: ColorControlMode,
+        value: i32,
+    ) -> Result<(), Error> {
+        let result = unsafe {
+            libk4a_sys::k4a_device_set_color_control(self.device_handle.0, command, mode, value)
+        };
+        k4a_result(result, "k4a_device_set_color_control", || {
+            format!("{:?}, mode: {:?}, value: {}", command, mode, value)
+        })
     }
 
     pub fn get_calibration(
@@ -25,14 +192,16 @@ impl Device {
         let mut calibration = std::mem::MaybeUninit::uninit();
         let result = unsafe {
             libk4a_sys::k4a_device_get_calibration(
-                self.device_handle,
+                self.device_handle.0,
                 depth_mode,
                 color_resolution,
                 calibration.as_mut_ptr(),
             )
         };
-        k4a_result(result)?;
-        Ok(unsafe { calibration.assume_init() })
+        k4a_result(result, "k4a_device_get_calibration", || {
+            format!("depth_mode: {:?}, color_resolution: {:?}", depth_mode, color_resolution)
+        })?;
+        Ok(Calibration::from_raw(unsafe { calibration.assume_init() }))
     }
 
     pub fn start_cameras(
@@ -43,32 +212,34 @@ impl Device {
     }
 }
 
-impl Drop for Device {
-    fn drop(&mut self) {
-        let device_handle = std::mem::replace(&mut self.device_handle, std::ptr::null_mut());
-        if device_handle.is_null() {
-            return;
-        }
-        unsafe { libk4a_sys::k4a_device_close(device_handle) };
+impl Deref for Device {
+    type Target = libk4a_sys::k4a_device_t;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device_handle.0
     }
 }
 
+/// A device with its cameras started. Captures keep the device open after it is dropped
+/// or stopped, but no new data arrives.
 pub struct RunningDevice {
     device: Device,
     depth_mode: DepthMode,
     color_resolution: ColorResolution,
+    imu_started: bool,
 }
 
 impl RunningDevice {
     pub fn start(device: Device, device_configuration: DeviceConfiguration) -> Result<Self, Error> {
         let result = unsafe {
-            libk4a_sys::k4a_device_start_cameras(device.device_handle, &device_configuration)
+            libk4a_sys::k4a_device_start_cameras(device.device_handle.0, &device_configuration)
         };
-        k4a_result(result)?;
+        k4a_result(result, "k4a_device_start_cameras", || format!("{:?}", device_configuration))?;
         Ok(RunningDevice {
             device,
             depth_mode: device_configuration.depth_mode,
             color_resolution: device_configuration.color_resolution,
+            imu_started: false,
         })
     }
 
@@ -80,29 +251,52 @@ impl RunningDevice {
     pub fn get_capture(&self, timeout: i32) -> Result<Capture, WaitError> {
         let mut capture_handle = std::ptr::null_mut();
         let wait_result = unsafe {
-            libk4a_sys::k4a_device_get_capture(self.device_handle, &mut capture_handle, timeout)
+            libk4a_sys::k4a_device_get_capture(self.device_handle.0, &mut capture_handle, timeout)
+        };
+        k4a_wait_result(wait_result, "k4a_device_get_capture", || format!("timeout: {}", timeout))?;
+        let owner: Arc<dyn Send + Sync> = self.device_handle.clone();
+        Ok(unsafe { Capture::from_owned_handle(capture_handle, Some(owner)) })
+    }
+
+    /// The IMU can only be started while the cameras are running. It is stopped along
+    /// with the cameras.
+    pub fn start_imu(&mut self) -> Result<(), Error> {
+        if self.imu_started {
+            return Ok(());
+        }
+        let result = unsafe { libk4a_sys::k4a_device_start_imu(self.device_handle.0) };
+        k4a_result(result, "k4a_device_start_imu", String::new)?;
+        self.imu_started = true;
+        Ok(())
+    }
+
+    pub fn get_imu_sample(&self, timeout: i32) -> Result<ImuSample, WaitError> {
+        let mut imu_sample = std::mem::MaybeUninit::uninit();
+        let wait_result = unsafe {
+            libk4a_sys::k4a_device_get_imu_sample(self.device_handle.0, imu_sample.as_mut_ptr(), timeout)
         };
-        k4a_wait_result(wait_result)?;
-        Ok(unsafe { Capture::from_handle(capture_handle) })
+        k4a_wait_result(wait_result, "k4a_device_get_imu_sample", || format!("timeout: {}", timeout))?;
+        Ok(unsafe { imu_sample.assume_init() })
     }
 
-    pub fn stop(mut self) -> Device {
-        let unsafe_clone = Device {
-            device_handle: self.device.device_handle,
+    pub fn stop(self) -> Device {
+        let device = Device {
+            device_handle: self.device.device_handle.clone(),
         };
-        let original = std::mem::replace(&mut self.device, unsafe_clone);
         drop(self);
-        original
+        device
     }
 }
 
 impl Drop for RunningDevice {
     fn drop(&mut self) {
-        let device_handle = self.device.device_handle;
-        if device_handle.is_null() {
-            return;
+        let device_handle = self.device.device_handle.0;
+        unsafe {
+            if self.imu_started {
+                libk4a_sys::k4a_device_stop_imu(device_handle);
+            }
+            libk4a_sys::k4a_device_stop_cameras(device_handle);
         }
-        unsafe { libk4a_sys::k4a_device_stop_cameras(device_handle) };
     }
 }
 
@@ -1,3 +1,4 @@
 pub type DeviceConfiguration = libk4a_sys::k4a_device_configuration_t;
 pub type DepthMode = libk4a_sys::k4a_depth_mode_t;
 pub type ColorResolution = libk4a_sys::k4a_color_resolution_t;
+pub type WiredSyncMode = libk4a_sys::k4a_wired_sync_mode_t;
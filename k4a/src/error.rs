@@ -1,66 +1,174 @@
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
-pub enum Error {
-    Failed,
+use std::ffi::CString;
+use std::fmt;
+use std::path::Path;
+
+use super::logging::{self, LogMessage};
+
+/// A failed SDK call, along with the log messages the SDK emitted right before it failed
+/// (if a debug message handler was installed with `set_debug_message_handler`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Error {
+    call: &'static str,
+    args: String,
+    log: Vec<LogMessage>,
+}
+
+impl Error {
+    pub fn new(call: &'static str, args: String) -> Self {
+        Self {
+            call,
+            args,
+            log: logging::take_recent(),
+        }
+    }
+
+    pub fn call(&self) -> &'static str {
+        self.call
+    }
+
+    pub fn args(&self) -> &str {
+        &self.args
+    }
+
+    pub fn log(&self) -> &[LogMessage] {
+        &self.log
+    }
 }
 
-impl From<libk4a_sys::k4a_result_t> for Error {
-    fn from(result: libk4a_sys::k4a_result_t) -> Self {
-        match result {
-            libk4a_sys::k4a_result_t::K4A_RESULT_FAILED => Error::Failed,
-            _ => unreachable!(),
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}({}) failed", self.call, self.args)?;
+        for message in &self.log {
+            write!(f, "\n  {}", message)?;
         }
+        Ok(())
     }
 }
 
-pub fn k4a_result(result: libk4a_sys::k4a_result_t) -> Result<(), Error> {
+impl std::error::Error for Error {}
+
+/// `s` as an argument to `call`, or an error if it has a NUL byte, which the SDK would
+/// take for its end.
+pub fn c_string(s: &str, call: &'static str) -> Result<CString, Error> {
+    CString::new(s).map_err(|_| Error {
+        call,
+        args: format!("{:?} contains a NUL byte", s),
+        // the SDK was not called, so nothing it logged is about this
+        log: Vec::new(),
+    })
+}
+
+/// `path` as an argument to `call`, or an error if it is not Unicode, rather than passing
+/// the SDK a mangled path.
+pub fn path_c_string(path: &Path, call: &'static str) -> Result<CString, Error> {
+    match path.to_str() {
+        Some(path) => c_string(path, call),
+        None => Err(Error {
+            call,
+            args: format!("{:?} is not valid Unicode", path),
+            log: Vec::new(),
+        }),
+    }
+}
+
+pub fn k4a_result<F>(result: libk4a_sys::k4a_result_t, call: &'static str, args: F) -> Result<(), Error>
+where
+    F: FnOnce() -> String,
+{
     match result {
         libk4a_sys::k4a_result_t::K4A_RESULT_SUCCEEDED => Ok(()),
-        _ => Err(result.into()),
+        _ => Err(Error::new(call, args())),
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum WaitError {
-    Failed,
+    Failed(Error),
     Timeout,
 }
 
-impl From<libk4a_sys::k4a_wait_result_t> for WaitError {
-    fn from(result: libk4a_sys::k4a_wait_result_t) -> Self {
-        match result {
-            libk4a_sys::k4a_wait_result_t::K4A_WAIT_RESULT_FAILED => WaitError::Failed,
-            libk4a_sys::k4a_wait_result_t::K4A_WAIT_RESULT_TIMEOUT => WaitError::Timeout,
-            _ => unreachable!(),
+impl fmt::Display for WaitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WaitError::Failed(e) => e.fmt(f),
+            WaitError::Timeout => write!(f, "timed out"),
         }
     }
 }
 
-pub fn k4a_wait_result(wait_result: libk4a_sys::k4a_wait_result_t) -> Result<(), WaitError> {
+impl std::error::Error for WaitError {}
+
+pub fn k4a_wait_result<F>(
+    wait_result: libk4a_sys::k4a_wait_result_t,
+    call: &'static str,
+    args: F,
+) -> Result<(), WaitError>
+where
+    F: FnOnce() -> String,
+{
     match wait_result {
         libk4a_sys::k4a_wait_result_t::K4A_WAIT_RESULT_SUCCEEDED => Ok(()),
-        _ => Err(wait_result.into()),
+        libk4a_sys::k4a_wait_result_t::K4A_WAIT_RESULT_TIMEOUT => Err(WaitError::Timeout),
+        _ => Err(WaitError::Failed(Error::new(call, args()))),
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OpenError {
+    Failed(Error),
+    /// No device that could be opened has this serial number.
+    NotFound(String),
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpenError::Failed(e) => e.fmt(f),
+            OpenError::NotFound(serial_number) => write!(
+                f,
+                "no device with serial number {:?} found, devices open in another process cannot be identified",
+                serial_number
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OpenError {}
+
+impl From<Error> for OpenError {
+    fn from(e: Error) -> Self {
+        OpenError::Failed(e)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum StreamError {
-    Failed,
+    Failed(Error),
     Eof,
 }
 
-impl From<libk4a_sys::k4a_stream_result_t> for StreamError {
-    fn from(result: libk4a_sys::k4a_stream_result_t) -> Self {
-        match result {
-            libk4a_sys::k4a_stream_result_t::K4A_STREAM_RESULT_FAILED => StreamError::Failed,
-            libk4a_sys::k4a_stream_result_t::K4A_STREAM_RESULT_EOF => StreamError::Eof,
-            _ => unreachable!(),
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Failed(e) => e.fmt(f),
+            StreamError::Eof => write!(f, "end of stream"),
         }
     }
 }
 
-pub fn k4a_stream_result(wait_result: libk4a_sys::k4a_stream_result_t) -> Result<(), StreamError> {
-    match wait_result {
+impl std::error::Error for StreamError {}
+
+pub fn k4a_stream_result<F>(
+    stream_result: libk4a_sys::k4a_stream_result_t,
+    call: &'static str,
+    args: F,
+) -> Result<(), StreamError>
+where
+    F: FnOnce() -> String,
+{
+    match stream_result {
         libk4a_sys::k4a_stream_result_t::K4A_STREAM_RESULT_SUCCEEDED => Ok(()),
-        _ => Err(wait_result.into()),
+        libk4a_sys::k4a_stream_result_t::K4A_STREAM_RESULT_EOF => Err(StreamError::Eof),
+        _ => Err(StreamError::Failed(Error::new(call, args()))),
     }
 }
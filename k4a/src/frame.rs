@@ -1,23 +1,37 @@
-use std::marker::PhantomData;
+use std::fmt;
 use std::ops::Deref;
 
 use super::body::Skeleton;
+use super::capture::Capture;
 use super::error::{k4a_result, Error};
+use super::image::Image;
+use super::owner::Owner;
 
-#[derive(Debug)]
-pub struct Frame<'d> {
+/// A reference counted body tracking result. It keeps the tracker it came from alive,
+/// and with it the devices and playbacks of the captures enqueued to that tracker.
+pub struct Frame {
     frame_handle: libk4a_sys::k4abt_frame_t,
-    _phantom: PhantomData<&'d ()>,
+    owner: Owner,
 }
 
-impl<'d> Frame<'d> {
+// The tracker never touches a result again once it is popped, the getters copy the
+// bodies out, and `k4abt_frame_release` counts atomically.
+unsafe impl Send for Frame {}
+unsafe impl Sync for Frame {}
+
+impl Frame {
     /// # Safety
     ///
-    /// Ensure `frame_handle` is unique, and not a null or not dangling
+    /// Ensure `frame_handle` is unique, and not a null or not dangling, and that the
+    /// tracker it came from outlives the returned `Frame`.
     pub unsafe fn from_handle(frame_handle: libk4a_sys::k4abt_frame_t) -> Self {
+        Self::from_owned_handle(frame_handle, None)
+    }
+
+    pub(crate) unsafe fn from_owned_handle(frame_handle: libk4a_sys::k4abt_frame_t, owner: Owner) -> Self {
         Self {
             frame_handle,
-            _phantom: PhantomData,
+            owner,
         }
     }
 
@@ -29,6 +43,10 @@ impl<'d> Frame<'d> {
         unsafe { libk4a_sys::k4abt_frame_get_body_id(self.frame_handle, index) }
     }
 
+    pub fn get_device_timestamp_usec(&self) -> u64 {
+        unsafe { libk4a_sys::k4abt_frame_get_device_timestamp_usec(self.frame_handle) }
+    }
+
     pub fn get_body_skeleton(&self, index: u32) -> Result<Skeleton, Error> {
         let mut skelton = std::mem::MaybeUninit::<Skeleton>::uninit();
         let result = unsafe {
@@ -38,21 +56,48 @@ impl<'d> Frame<'d> {
                 skelton.as_mut_ptr(),
             )
         };
-        k4a_result(result)?;
+        k4a_result(result, "k4abt_frame_get_body_skeleton", || format!("index: {}", index))?;
         Ok(unsafe { skelton.assume_init() })
     }
+
+    /// Each pixel holds the index of the body it belongs to (not its id), or
+    /// `BODY_INDEX_MAP_BACKGROUND`.
+    pub fn body_index_map(&self) -> Option<Image> {
+        unsafe {
+            Image::from_nullable_handle(
+                libk4a_sys::k4abt_frame_get_body_index_map(self.frame_handle),
+                self.owner.clone(),
+            )
+        }
+    }
+
+    /// The capture this frame was computed from.
+    pub fn capture(&self) -> Option<Capture> {
+        let capture_handle = unsafe { libk4a_sys::k4abt_frame_get_capture(self.frame_handle) };
+        if capture_handle.is_null() {
+            None
+        } else {
+            Some(unsafe { Capture::from_owned_handle(capture_handle, self.owner.clone()) })
+        }
+    }
 }
 
-impl<'d> Clone for Frame<'d> {
+impl Clone for Frame {
     fn clone(&self) -> Self {
         unsafe {
             libk4a_sys::k4abt_frame_reference(self.frame_handle);
-            Self::from_handle(self.frame_handle)
+            Self::from_owned_handle(self.frame_handle, self.owner.clone())
         }
     }
 }
 
-impl<'d> Drop for Frame<'d> {
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Frame").field("frame_handle", &self.frame_handle).finish()
+    }
+}
+
+impl Drop for Frame {
     fn drop(&mut self) {
         let frame_handle = self.frame_handle;
         if frame_handle.is_null() {
@@ -65,7 +110,7 @@ impl<'d> Drop for Frame<'d> {
     }
 }
 
-impl<'d> Deref for Frame<'d> {
+impl Deref for Frame {
     type Target = libk4a_sys::k4abt_frame_t;
 
     fn deref(&self) -> &Self::Target {
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::ops::Deref;
+
+use super::error::{k4a_result, Error};
+use super::owner::Owner;
+
+pub type ImageFormat = libk4a_sys::k4a_image_format_t;
+
+/// A reference counted image. It keeps the device, playback or tracker it came from open.
+pub struct Image {
+    image_handle: libk4a_sys::k4a_image_t,
+    owner: Owner,
+}
+
+// `get_buffer` and the pixel getters only read the buffer, nothing here hands out a
+// mutable one, and `k4a_image_release` counts atomically.
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Image {
+    /// # Safety
+    ///
+    /// Ensure `image_handle` is a valid image whose reference is handed over, and that
+    /// whatever produced it outlives the returned `Image`.
+    pub unsafe fn from_handle(image_handle: libk4a_sys::k4a_image_t) -> Self {
+        Self {
+            image_handle,
+            owner: None,
+        }
+    }
+
+    /// Allocates an image owned by the caller, e.g. as the output of a `Transformation`.
+    pub fn create(
+        format: ImageFormat,
+        width_pixels: i32,
+        height_pixels: i32,
+        stride_bytes: i32,
+    ) -> Result<Image, Error> {
+        let mut image_handle = std::ptr::null_mut();
+        let result = unsafe {
+            libk4a_sys::k4a_image_create(format, width_pixels, height_pixels, stride_bytes, &mut image_handle)
+        };
+        k4a_result(result, "k4a_image_create", || {
+            format!("{:?}, {}x{}, stride: {}", format, width_pixels, height_pixels, stride_bytes)
+        })?;
+        Ok(unsafe { Image::from_handle(image_handle) })
+    }
+
+    /// Takes ownership of a handle returned by the SDK, which is null when there is no image.
+    pub(crate) unsafe fn from_nullable_handle(image_handle: libk4a_sys::k4a_image_t, owner: Owner) -> Option<Self> {
+        if image_handle.is_null() {
+            None
+        } else {
+            Some(Self { image_handle, owner })
+        }
+    }
+
+    pub fn get_format(&self) -> ImageFormat {
+        unsafe { libk4a_sys::k4a_image_get_format(self.image_handle) }
+    }
+
+    pub fn get_width_pixels(&self) -> i32 {
+        unsafe { libk4a_sys::k4a_image_get_width_pixels(self.image_handle) }
+    }
+
+    pub fn get_height_pixels(&self) -> i32 {
+        unsafe { libk4a_sys::k4a_image_get_height_pixels(self.image_handle) }
+    }
+
+    /// Zero for compressed formats such as MJPG.
+    pub fn get_stride_bytes(&self) -> i32 {
+        unsafe { libk4a_sys::k4a_image_get_stride_bytes(self.image_handle) }
+    }
+
+    pub fn get_device_timestamp_usec(&self) -> u64 {
+        unsafe { libk4a_sys::k4a_image_get_device_timestamp_usec(self.image_handle) }
+    }
+
+    pub fn get_system_timestamp_nsec(&self) -> u64 {
+        unsafe { libk4a_sys::k4a_image_get_system_timestamp_nsec(self.image_handle) }
+    }
+
+    pub fn get_buffer(&self) -> &[u8] {
+        unsafe {
+            let buffer = libk4a_sys::k4a_image_get_buffer(self.image_handle);
+            let size = libk4a_sys::k4a_image_get_size(self.image_handle);
+            if buffer.is_null() || size == 0 {
+                return &[];
+            }
+            std::slice::from_raw_parts(buffer, size)
+        }
+    }
+
+    /// Returns the pixel at (`x`, `y`) of a DEPTH16 or IR16 image.
+    pub fn get_u16(&self, x: i32, y: i32) -> Option<u16> {
+        let offset = self.pixel_offset(x, y, 2)?;
+        let bytes = self.get_buffer().get(offset..offset + 2)?;
+        Some(u16::from_ne_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Returns the (x, y, z) point at (`x`, `y`) of a point cloud image.
+    pub fn get_xyz(&self, x: i32, y: i32) -> Option<(i16, i16, i16)> {
+        let offset = self.pixel_offset(x, y, 6)?;
+        let b = self.get_buffer().get(offset..offset + 6)?;
+        Some((
+            i16::from_ne_bytes([b[0], b[1]]),
+            i16::from_ne_bytes([b[2], b[3]]),
+            i16::from_ne_bytes([b[4], b[5]]),
+        ))
+    }
+
+    /// Returns the pixel at (`x`, `y`) of a CUSTOM8 image such as the body index map.
+    pub fn get_u8(&self, x: i32, y: i32) -> Option<u8> {
+        let offset = self.pixel_offset(x, y, 1)?;
+        self.get_buffer().get(offset).copied()
+    }
+
+    fn pixel_offset(&self, x: i32, y: i32, pixel_size: usize) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.get_width_pixels() || y >= self.get_height_pixels() {
+            return None;
+        }
+        Some(y as usize * self.get_stride_bytes() as usize + x as usize * pixel_size)
+    }
+}
+
+impl Clone for Image {
+    fn clone(&self) -> Self {
+        unsafe {
+            libk4a_sys::k4a_image_reference(self.image_handle);
+        }
+        Self {
+            image_handle: self.image_handle,
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Image").field("image_handle", &self.image_handle).finish()
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        let image_handle = self.image_handle;
+        if image_handle.is_null() {
+            return;
+        }
+        unsafe {
+            libk4a_sys::k4a_image_release(image_handle);
+        }
+        self.image_handle = std::ptr::null_mut();
+    }
+}
+
+impl Deref for Image {
+    type Target = libk4a_sys::k4a_image_t;
+
+    fn deref(&self) -> &Self::Target {
+        &self.image_handle
+    }
+}
@@ -0,0 +1,3 @@
+/// Accelerometer (m/s², in the accel sensor's coordinate system) and gyroscope (rad/s)
+/// readings taken together.
+pub type ImuSample = libk4a_sys::k4a_imu_sample_t;
@@ -1,23 +1,78 @@
+//! Safe wrappers for the Azure Kinect Sensor and Body Tracking SDKs.
+//!
+//! Threading: `Device`, `RunningDevice` and `Tracker` are `Send` and `Sync`, so a
+//! capture thread and a tracking thread can share them. `Capture`, `Image` and `Frame`
+//! are reference counted and keep the device, playback or tracker that produced them
+//! open, so they can outlive it and move freely between threads. A `Frame` also keeps
+//! open the devices and playbacks whose captures went into its tracker. `Playback`,
+//! `Recorder` and `Transformation` are `Send` only.
+//!
+//! Without the default `sdk` feature only the body tracking types are available, and
+//! neither SDK is needed to build.
+
 mod body;
+#[cfg(feature = "sdk")]
 mod calibration;
+#[cfg(feature = "sdk")]
 mod capture;
+#[cfg(feature = "sdk")]
 mod device;
+#[cfg(feature = "sdk")]
 mod device_configuration;
+#[cfg(feature = "sdk")]
 mod error;
+#[cfg(feature = "sdk")]
 mod frame;
+#[cfg(feature = "sdk")]
+mod image;
+#[cfg(feature = "sdk")]
+mod imu;
+#[cfg(feature = "sdk")]
+mod logging;
+#[cfg(feature = "sdk")]
+mod owner;
+#[cfg(feature = "sdk")]
 mod tracker;
+#[cfg(feature = "sdk")]
+mod transformation;
+#[cfg(feature = "sdk")]
 mod tracker_configuration;
+#[cfg(feature = "sdk")]
 mod playback;
+#[cfg(feature = "sdk")]
+mod record;
 
 pub use body::{
-    Body, Float2, Float3, Joint, Quaternion, Skeleton, joint_id,
+    Body, Float2, Float3, Joint, JointConfidenceLevel, Quaternion, Skeleton, joint_id, BODY_INDEX_MAP_BACKGROUND,
 };
-pub use calibration::Calibration;
+#[cfg(feature = "sdk")]
+pub use calibration::{Calibration, CalibrationType};
+#[cfg(feature = "sdk")]
 pub use capture::Capture;
-pub use device::{Device, RunningDevice};
-pub use device_configuration::{ColorResolution, DepthMode, DeviceConfiguration};
-pub use error::{Error, WaitError, StreamError};
+#[cfg(feature = "sdk")]
+pub use device::{
+    ColorControlCapabilities, ColorControlCommand, ColorControlMode, Device, DeviceInfo, HardwareVersion,
+    RunningDevice, Version,
+};
+#[cfg(feature = "sdk")]
+pub use device_configuration::{ColorResolution, DepthMode, DeviceConfiguration, WiredSyncMode};
+#[cfg(feature = "sdk")]
+pub use error::{Error, OpenError, WaitError, StreamError};
+#[cfg(feature = "sdk")]
 pub use frame::Frame;
+#[cfg(feature = "sdk")]
+pub use image::{Image, ImageFormat};
+#[cfg(feature = "sdk")]
+pub use imu::ImuSample;
+#[cfg(feature = "sdk")]
+pub use logging::{set_debug_message_handler, LogLevel, LogMessage};
+#[cfg(feature = "sdk")]
 pub use tracker::Tracker;
-pub use tracker_configuration::TrackerConfiguration;
-pub use playback::Playback;
+#[cfg(feature = "sdk")]
+pub use transformation::Transformation;
+#[cfg(feature = "sdk")]
+pub use tracker_configuration::{SensorOrientation, TrackerConfiguration, TrackerProcessingMode};
+#[cfg(feature = "sdk")]
+pub use playback::{Playback, RecordConfiguration, SeekOrigin};
+#[cfg(feature = "sdk")]
+pub use record::Recorder;
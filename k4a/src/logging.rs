@@ -0,0 +1,101 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{Arc, Mutex};
+
+use super::error::{k4a_result, Error};
+
+pub type LogLevel = libk4a_sys::k4a_log_level_t;
+
+// messages kept around to be attached to the next `Error`
+const RECENT_CAPACITY: usize = 16;
+
+type Sink = Arc<dyn Fn(&LogMessage) + Send + Sync>;
+
+thread_local! {
+    // the SDK logs a failure on the thread of the call that failed, so keeping messages
+    // per thread attaches them to the right `Error` when devices run on several threads
+    static RECENT: RefCell<VecDeque<LogMessage>> = const { RefCell::new(VecDeque::new()) };
+}
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LogMessage {
+    pub level: LogLevel,
+    pub file: String,
+    pub line: i32,
+    pub message: String,
+}
+
+impl fmt::Display for LogMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level {
+            LogLevel::K4A_LOG_LEVEL_CRITICAL => "critical",
+            LogLevel::K4A_LOG_LEVEL_ERROR => "error",
+            LogLevel::K4A_LOG_LEVEL_WARNING => "warning",
+            LogLevel::K4A_LOG_LEVEL_INFO => "info",
+            _ => "trace",
+        };
+        write!(f, "[k4a {}] {} ({}:{})", level, self.message, self.file, self.line)
+    }
+}
+
+/// Routes the SDK's log messages at `min_level` or above to `sink`. The messages are
+/// also attached to the next `Error` returned by this crate on the thread that logged
+/// them.
+pub fn set_debug_message_handler<F>(min_level: LogLevel, sink: F) -> Result<(), Error>
+where
+    F: Fn(&LogMessage) + Send + Sync + 'static,
+{
+    *SINK.lock().unwrap() = Some(Arc::new(sink));
+    let result = unsafe {
+        libk4a_sys::k4a_set_debug_message_handler(
+            Some(debug_message_handler),
+            std::ptr::null_mut(),
+            min_level,
+        )
+    };
+    k4a_result(result, "k4a_set_debug_message_handler", || format!("min_level: {:?}", min_level))
+}
+
+pub(crate) fn take_recent() -> Vec<LogMessage> {
+    RECENT
+        .try_with(|recent| recent.borrow_mut().drain(..).collect())
+        .unwrap_or_default()
+}
+
+unsafe extern "C" fn debug_message_handler(
+    _context: *mut c_void,
+    level: LogLevel,
+    file: *const c_char,
+    line: c_int,
+    message: *const c_char,
+) {
+    let to_string = |s: *const c_char| {
+        if s.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(s).to_string_lossy().trim_end().to_string()
+        }
+    };
+    let message = LogMessage {
+        level,
+        file: to_string(file),
+        line,
+        message: to_string(message),
+    };
+    // called without the lock held, so that it may log or call into the SDK itself
+    let sink = SINK.lock().ok().and_then(|sink| sink.clone());
+    if let Some(sink) = sink {
+        sink(&message);
+    }
+    let _ = RECENT.try_with(|recent| {
+        let mut recent = recent.borrow_mut();
+        if recent.len() == RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(message);
+    });
+}
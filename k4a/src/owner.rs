@@ -0,0 +1,7 @@
+use std::sync::Arc;
+
+/// Keeps whatever produced a capture, image or body frame (a device, playback or
+/// tracker) open for as long as the data is referenced. `None` for data the caller
+/// created or whose handle was passed to an unsafe `from_handle`. See the crate
+/// documentation for how this lets data move between threads.
+pub(crate) type Owner = Option<Arc<dyn Send + Sync>>;
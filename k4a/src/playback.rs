@@ -1,50 +1,154 @@
-use std::ffi::CString;
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::sync::Arc;
 
 use super::calibration::Calibration;
 use super::capture::Capture;
-use super::error::{k4a_result, k4a_stream_result, Error, StreamError};
+use super::error::{c_string, k4a_result, k4a_stream_result, Error, StreamError};
+use super::imu::ImuSample;
 
+pub type RecordConfiguration = libk4a_sys::k4a_record_configuration_t;
+pub type SeekOrigin = libk4a_sys::k4a_playback_seek_origin_t;
+
+// Shared by a `Playback` and the captures read from it.
+struct PlaybackHandle(libk4a_sys::k4a_playback_t);
+
+// Only `Playback` calls into the handle, and it is not `Sync`. Captures merely hold the
+// handle, so `Sync` is only needed to keep them shareable, and `Drop` closes it once,
+// on whichever thread lets go of it last.
+unsafe impl Send for PlaybackHandle {}
+unsafe impl Sync for PlaybackHandle {}
+
+impl Drop for PlaybackHandle {
+    fn drop(&mut self) {
+        unsafe { libk4a_sys::k4a_playback_close(self.0) };
+    }
+}
+
+/// Reads a recording. Not `Sync`, since reading moves the playback's position.
 pub struct Playback {
-    playback_handle: libk4a_sys::k4a_playback_t,
+    playback_handle: Arc<PlaybackHandle>,
+    _not_sync: PhantomData<Cell<()>>,
 }
 
 impl Playback {
     pub fn open(path: &str) -> Result<Self, Error> {
-        let cstring = CString::new(path).unwrap();
+        let cstring = c_string(path, "k4a_playback_open")?;
         let mut playback_handle = std::ptr::null_mut();
         let result = unsafe { libk4a_sys::k4a_playback_open(cstring.as_ptr(), &mut playback_handle) };
-        k4a_result(result)?;
-        Ok(Playback { playback_handle })
+        k4a_result(result, "k4a_playback_open", || format!("path: {:?}", path))?;
+        Ok(Playback {
+            playback_handle: Arc::new(PlaybackHandle(playback_handle)),
+            _not_sync: PhantomData,
+        })
     }
 
     pub fn get_calibration(&self) -> Result<Calibration, Error> {
         let mut calibration = std::mem::MaybeUninit::uninit();
         let result = unsafe {
             libk4a_sys::k4a_playback_get_calibration(
-                self.playback_handle,
+                self.playback_handle.0,
                 calibration.as_mut_ptr(),
             )
         };
-        k4a_result(result)?;
-        Ok(unsafe { calibration.assume_init() })
+        k4a_result(result, "k4a_playback_get_calibration", String::new)?;
+        Ok(Calibration::from_raw(unsafe { calibration.assume_init() }))
+    }
+
+    fn owned_capture(&self, capture_handle: libk4a_sys::k4a_capture_t) -> Capture {
+        let owner: Arc<dyn Send + Sync> = self.playback_handle.clone();
+        unsafe { Capture::from_owned_handle(capture_handle, Some(owner)) }
+    }
+
+    pub fn get_record_configuration(&self) -> Result<RecordConfiguration, Error> {
+        let mut config = std::mem::MaybeUninit::uninit();
+        let result = unsafe {
+            libk4a_sys::k4a_playback_get_record_configuration(self.playback_handle.0, config.as_mut_ptr())
+        };
+        k4a_result(result, "k4a_playback_get_record_configuration", String::new)?;
+        Ok(unsafe { config.assume_init() })
+    }
+
+    pub fn get_recording_length_usec(&self) -> u64 {
+        unsafe { libk4a_sys::k4a_playback_get_recording_length_usec(self.playback_handle.0) }
+    }
+
+    /// Returns `None` if the recording has no tag called `name`.
+    pub fn get_tag(&self, name: &str) -> Result<Option<String>, Error> {
+        let cname = c_string(name, "k4a_playback_get_tag")?;
+        let mut size = 0;
+        let result = unsafe {
+            libk4a_sys::k4a_playback_get_tag(self.playback_handle.0, cname.as_ptr(), std::ptr::null_mut(), &mut size)
+        };
+        match result {
+            libk4a_sys::k4a_buffer_result_t::K4A_BUFFER_RESULT_TOO_SMALL => {}
+            libk4a_sys::k4a_buffer_result_t::K4A_BUFFER_RESULT_FAILED => return Ok(None),
+            _ => return Ok(Some(String::new())),
+        }
+        let mut value = vec![0u8; size];
+        let result = unsafe {
+            libk4a_sys::k4a_playback_get_tag(
+                self.playback_handle.0,
+                cname.as_ptr(),
+                value.as_mut_ptr() as *mut _,
+                &mut size,
+            )
+        };
+        if result != libk4a_sys::k4a_buffer_result_t::K4A_BUFFER_RESULT_SUCCEEDED {
+            return Err(Error::new("k4a_playback_get_tag", format!("name: {:?}", name)));
+        }
+        let value = CStr::from_bytes_until_nul(&value)
+            .map(|value| value.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Some(value))
+    }
+
+    /// Moves the read position of every track. `offset_usec` is relative to the start or
+    /// the end of the recording, or is a device timestamp, depending on `origin`.
+    pub fn seek_timestamp(&self, offset_usec: i64, origin: SeekOrigin) -> Result<(), Error> {
+        let result = unsafe {
+            libk4a_sys::k4a_playback_seek_timestamp(self.playback_handle.0, offset_usec, origin)
+        };
+        k4a_result(result, "k4a_playback_seek_timestamp", || {
+            format!("offset_usec: {}, origin: {:?}", offset_usec, origin)
+        })
     }
 
     pub fn get_capture(&self) -> Result<Capture, StreamError> {
         let mut capture_handle = std::ptr::null_mut();
         let wait_result = unsafe {
-            libk4a_sys::k4a_playback_get_next_capture(self.playback_handle, &mut capture_handle)
+            libk4a_sys::k4a_playback_get_next_capture(self.playback_handle.0, &mut capture_handle)
         };
-        k4a_stream_result(wait_result)?;
-        Ok(unsafe { Capture::from_handle(capture_handle) })
+        k4a_stream_result(wait_result, "k4a_playback_get_next_capture", String::new)?;
+        Ok(self.owned_capture(capture_handle))
     }
-}
 
-impl Drop for Playback {
-    fn drop(&mut self) {
-        let playback_handle = std::mem::replace(&mut self.playback_handle, std::ptr::null_mut());
-        if playback_handle.is_null() {
-            return;
-        }
-        unsafe { libk4a_sys::k4a_playback_close(playback_handle) };
+    pub fn get_previous_capture(&self) -> Result<Capture, StreamError> {
+        let mut capture_handle = std::ptr::null_mut();
+        let stream_result = unsafe {
+            libk4a_sys::k4a_playback_get_previous_capture(self.playback_handle.0, &mut capture_handle)
+        };
+        k4a_stream_result(stream_result, "k4a_playback_get_previous_capture", String::new)?;
+        Ok(self.owned_capture(capture_handle))
+    }
+
+    /// Fails with `StreamError::Eof` right away if the recording has no IMU track.
+    pub fn get_next_imu_sample(&self) -> Result<ImuSample, StreamError> {
+        let mut imu_sample = std::mem::MaybeUninit::uninit();
+        let stream_result = unsafe {
+            libk4a_sys::k4a_playback_get_next_imu_sample(self.playback_handle.0, imu_sample.as_mut_ptr())
+        };
+        k4a_stream_result(stream_result, "k4a_playback_get_next_imu_sample", String::new)?;
+        Ok(unsafe { imu_sample.assume_init() })
+    }
+
+    pub fn get_previous_imu_sample(&self) -> Result<ImuSample, StreamError> {
+        let mut imu_sample = std::mem::MaybeUninit::uninit();
+        let stream_result = unsafe {
+            libk4a_sys::k4a_playback_get_previous_imu_sample(self.playback_handle.0, imu_sample.as_mut_ptr())
+        };
+        k4a_stream_result(stream_result, "k4a_playback_get_previous_imu_sample", String::new)?;
+        Ok(unsafe { imu_sample.assume_init() })
     }
 }
@@ -0,0 +1,166 @@
+use super::capture::Capture;
+use super::device::Device;
+use super::device_configuration::DeviceConfiguration;
+use super::error::{c_string, k4a_result, Error};
+use super::imu::ImuSample;
+
+/// Writes captures to an MKV file. Tags, tracks and attachments have to be added before
+/// `write_header`, and data can only be written after it.
+pub struct Recorder {
+    recording_handle: libk4a_sys::k4a_record_t,
+}
+
+// The recording is not bound to the thread that created it. Its methods take `&self`
+// but are not synchronized, so `Recorder` must not be `Sync`.
+unsafe impl Send for Recorder {}
+
+impl Recorder {
+    /// `device` is used to store its calibration and serial number in the recording,
+    /// and may be `None` when recording generated data.
+    pub fn create(
+        path: &str,
+        device: Option<&Device>,
+        device_configuration: &DeviceConfiguration,
+    ) -> Result<Self, Error> {
+        let cpath = c_string(path, "k4a_record_create")?;
+        let device_handle = device.map(|device| **device).unwrap_or(std::ptr::null_mut());
+        let mut recording_handle = std::ptr::null_mut();
+        let result = unsafe {
+            libk4a_sys::k4a_record_create(
+                cpath.as_ptr(),
+                device_handle,
+                device_configuration.clone(),
+                &mut recording_handle,
+            )
+        };
+        k4a_result(result, "k4a_record_create", || {
+            format!("path: {:?}, {:?}", path, device_configuration)
+        })?;
+        Ok(Recorder { recording_handle })
+    }
+
+    pub fn add_tag(&self, name: &str, value: &str) -> Result<(), Error> {
+        let cname = c_string(name, "k4a_record_add_tag")?;
+        let cvalue = c_string(value, "k4a_record_add_tag")?;
+        let result = unsafe {
+            libk4a_sys::k4a_record_add_tag(self.recording_handle, cname.as_ptr(), cvalue.as_ptr())
+        };
+        k4a_result(result, "k4a_record_add_tag", || format!("name: {:?}, value: {:?}", name, value))
+    }
+
+    pub fn add_imu_track(&self) -> Result<(), Error> {
+        let result = unsafe { libk4a_sys::k4a_record_add_imu_track(self.recording_handle) };
+        k4a_result(result, "k4a_record_add_imu_track", String::new)
+    }
+
+    pub fn add_attachment(&self, attachment_name: &str, buffer: &[u8]) -> Result<(), Error> {
+        let cname = c_string(attachment_name, "k4a_record_add_attachment")?;
+        let result = unsafe {
+            libk4a_sys::k4a_record_add_attachment(
+                self.recording_handle,
+                cname.as_ptr(),
+                buffer.as_ptr(),
+                buffer.len(),
+            )
+        };
+        k4a_result(result, "k4a_record_add_attachment", || {
+            format!("attachment_name: {:?}, {} bytes", attachment_name, buffer.len())
+        })
+    }
+
+    /// Adds a track of arbitrary data, written with `write_custom_track_data`. Set
+    /// `high_freq_data` when writing more often than the camera frame rate.
+    pub fn add_custom_subtitle_track(
+        &self,
+        track_name: &str,
+        codec_id: &str,
+        codec_context: &[u8],
+        high_freq_data: bool,
+    ) -> Result<(), Error> {
+        let ctrack_name = c_string(track_name, "k4a_record_add_custom_subtitle_track")?;
+        let ccodec_id = c_string(codec_id, "k4a_record_add_custom_subtitle_track")?;
+        let track_settings = libk4a_sys::k4a_record_subtitle_settings_t { high_freq_data };
+        let result = unsafe {
+            libk4a_sys::k4a_record_add_custom_subtitle_track(
+                self.recording_handle,
+                ctrack_name.as_ptr(),
+                ccodec_id.as_ptr(),
+                // only read by the SDK
+                codec_context.as_ptr() as *mut u8,
+                codec_context.len(),
+                &track_settings,
+            )
+        };
+        k4a_result(result, "k4a_record_add_custom_subtitle_track", || {
+            format!("track_name: {:?}, codec_id: {:?}", track_name, codec_id)
+        })
+    }
+
+    pub fn write_header(&self) -> Result<(), Error> {
+        let result = unsafe { libk4a_sys::k4a_record_write_header(self.recording_handle) };
+        k4a_result(result, "k4a_record_write_header", String::new)
+    }
+
+    pub fn write_capture(&self, capture: &Capture) -> Result<(), Error> {
+        let result = unsafe { libk4a_sys::k4a_record_write_capture(self.recording_handle, **capture) };
+        k4a_result(result, "k4a_record_write_capture", String::new)
+    }
+
+    pub fn write_imu_sample(&self, imu_sample: &ImuSample) -> Result<(), Error> {
+        let imu_sample = ImuSample {
+            temperature: imu_sample.temperature,
+            acc_sample: imu_sample.acc_sample.clone(),
+            acc_timestamp_usec: imu_sample.acc_timestamp_usec,
+            gyro_sample: imu_sample.gyro_sample.clone(),
+            gyro_timestamp_usec: imu_sample.gyro_timestamp_usec,
+        };
+        let result = unsafe { libk4a_sys::k4a_record_write_imu_sample(self.recording_handle, imu_sample) };
+        k4a_result(result, "k4a_record_write_imu_sample", String::new)
+    }
+
+    pub fn write_custom_track_data(
+        &self,
+        track_name: &str,
+        device_timestamp_usec: u64,
+        custom_data: &[u8],
+    ) -> Result<(), Error> {
+        let ctrack_name = c_string(track_name, "k4a_record_write_custom_track_data")?;
+        let result = unsafe {
+            libk4a_sys::k4a_record_write_custom_track_data(
+                self.recording_handle,
+                ctrack_name.as_ptr(),
+                device_timestamp_usec,
+                // only read by the SDK
+                custom_data.as_ptr() as *mut u8,
+                custom_data.len(),
+            )
+        };
+        k4a_result(result, "k4a_record_write_custom_track_data", || {
+            format!("track_name: {:?}, device_timestamp_usec: {}", track_name, device_timestamp_usec)
+        })
+    }
+
+    pub fn flush(&self) -> Result<(), Error> {
+        let result = unsafe { libk4a_sys::k4a_record_flush(self.recording_handle) };
+        k4a_result(result, "k4a_record_flush", String::new)
+    }
+
+    /// Flushes and finalizes the file. Dropping the recorder does the same, but cannot
+    /// report a failed flush.
+    pub fn close(self) -> Result<(), Error> {
+        self.flush()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let recording_handle = self.recording_handle;
+        if recording_handle.is_null() {
+            return;
+        }
+        unsafe {
+            libk4a_sys::k4a_record_close(recording_handle);
+        }
+        self.recording_handle = std::ptr::null_mut();
+    }
+}
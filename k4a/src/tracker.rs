@@ -1,11 +1,33 @@
+use std::sync::{Arc, Mutex};
+
 use super::calibration::Calibration;
 use super::capture::Capture;
-use super::error::{k4a_result, k4a_wait_result, Error, WaitError};
+use super::error::{k4a_result, k4a_wait_result, path_c_string, Error, WaitError};
 use super::frame::Frame;
 use super::tracker_configuration::TrackerConfiguration;
 
+// Shared by a `Tracker` and the frames popped from it. Also holds the owners of the
+// captures enqueued so far, since frames hand those captures out again; they are
+// released after the tracker is destroyed.
+struct TrackerHandle(libk4a_sys::k4abt_tracker_t, Mutex<Vec<Arc<dyn Send + Sync>>>);
+
+// The Body Tracking SDK is built for enqueueing captures on one thread while popping
+// results on another, and `set_temporal_smoothing` only swaps a setting.
+unsafe impl Send for TrackerHandle {}
+unsafe impl Sync for TrackerHandle {}
+
+impl Drop for TrackerHandle {
+    fn drop(&mut self) {
+        unsafe {
+            libk4a_sys::k4abt_tracker_shutdown(self.0);
+            libk4a_sys::k4abt_tracker_destroy(self.0);
+        }
+    }
+}
+
+/// Captures can be enqueued on one thread while results are popped on another.
 pub struct Tracker {
-    tracker_handle: libk4a_sys::k4abt_tracker_t,
+    tracker_handle: Arc<TrackerHandle>,
 }
 
 impl Tracker {
@@ -13,46 +35,60 @@ impl Tracker {
         calibration: &Calibration,
         tracker_configuration: TrackerConfiguration,
     ) -> Result<Self, Error> {
+        let args = format!("{:?}", tracker_configuration);
+        let model_path = match &tracker_configuration.model_path {
+            Some(path) => Some(path_c_string(path, "k4abt_tracker_create")?),
+            None => None,
+        };
+        let raw_configuration = libk4a_sys::k4abt_tracker_configuration_t {
+            sensor_orientation: tracker_configuration.sensor_orientation,
+            processing_mode: tracker_configuration.processing_mode,
+            gpu_device_id: tracker_configuration.gpu_device_id,
+            model_path: model_path.as_ref().map_or(std::ptr::null(), |path| path.as_ptr()),
+        };
         let mut tracker_handle = std::ptr::null_mut();
         let result = unsafe {
             libk4a_sys::k4abt_tracker_create(
-                calibration,
-                tracker_configuration,
+                calibration.as_ptr(),
+                raw_configuration,
                 &mut tracker_handle,
             )
         };
-        k4a_result(result)?;
-        Ok(Tracker { tracker_handle })
+        k4a_result(result, "k4abt_tracker_create", || args)?;
+        Ok(Tracker {
+            tracker_handle: Arc::new(TrackerHandle(tracker_handle, Mutex::new(Vec::new()))),
+        })
     }
 
     pub fn enqueue_capture(&self, capture: Capture, timeout: i32) -> Result<(), WaitError> {
         let wait_result = unsafe {
-            libk4a_sys::k4abt_tracker_enqueue_capture(self.tracker_handle, *capture, timeout)
+            libk4a_sys::k4abt_tracker_enqueue_capture(self.tracker_handle.0, *capture, timeout)
         };
-        k4a_wait_result(wait_result)?;
+        k4a_wait_result(wait_result, "k4abt_tracker_enqueue_capture", || format!("timeout: {}", timeout))?;
+        if let Some(owner) = capture.owner() {
+            let mut owners = self.tracker_handle.1.lock().unwrap();
+            if !owners.iter().any(|known| Arc::ptr_eq(known, owner)) {
+                owners.push(owner.clone());
+            }
+        }
         Ok(())
     }
 
+    /// Blends each joint with its position in the previous frame. `smoothing_factor` ranges
+    /// from 0 (no smoothing) to 1 (joints never move), the SDK defaults to 0.
+    pub fn set_temporal_smoothing(&self, smoothing_factor: f32) {
+        unsafe {
+            libk4a_sys::k4abt_tracker_set_temporal_smoothing(self.tracker_handle.0, smoothing_factor);
+        }
+    }
+
     pub fn k4abt_tracker_pop_result(&self, timeout: i32) -> Result<Frame, WaitError> {
         let mut frame_handle = std::ptr::null_mut();
         let wait_result = unsafe {
-            libk4a_sys::k4abt_tracker_pop_result(self.tracker_handle, &mut frame_handle, timeout)
+            libk4a_sys::k4abt_tracker_pop_result(self.tracker_handle.0, &mut frame_handle, timeout)
         };
-        k4a_wait_result(wait_result)?;
-        Ok(unsafe { Frame::from_handle(frame_handle) })
-    }
-}
-
-impl Drop for Tracker {
-    fn drop(&mut self) {
-        let tracker_handle = self.tracker_handle;
-        if tracker_handle.is_null() {
-            return;
-        }
-        unsafe {
-            libk4a_sys::k4abt_tracker_shutdown(tracker_handle);
-            libk4a_sys::k4abt_tracker_destroy(tracker_handle);
-        }
-        self.tracker_handle = std::ptr::null_mut();
+        k4a_wait_result(wait_result, "k4abt_tracker_pop_result", || format!("timeout: {}", timeout))?;
+        let owner: Arc<dyn Send + Sync> = self.tracker_handle.clone();
+        Ok(unsafe { Frame::from_owned_handle(frame_handle, Some(owner)) })
     }
 }
@@ -1,3 +1,27 @@
-pub type TrackerConfiguration = libk4a_sys::k4abt_tracker_configuration_t;
+use std::path::PathBuf;
+
 pub type SensorOrientation = libk4a_sys::k4abt_sensor_orientation_t;
 pub type TrackerProcessingMode = libk4a_sys::k4abt_tracker_processing_mode_t;
+
+#[derive(Debug, Clone)]
+pub struct TrackerConfiguration {
+    pub sensor_orientation: SensorOrientation,
+    pub processing_mode: TrackerProcessingMode,
+    /// Ignored in `K4ABT_TRACKER_PROCESSING_MODE_CPU`.
+    pub gpu_device_id: i32,
+    /// The ONNX model to track with, e.g. `dnn_model_2_0_lite_op11.onnx`. `None` uses the
+    /// SDK's default (full) model.
+    pub model_path: Option<PathBuf>,
+}
+
+impl Default for TrackerConfiguration {
+    fn default() -> Self {
+        let raw = libk4a_sys::k4abt_tracker_configuration_t::default();
+        Self {
+            sensor_orientation: raw.sensor_orientation,
+            processing_mode: raw.processing_mode,
+            gpu_device_id: raw.gpu_device_id,
+            model_path: None,
+        }
+    }
+}
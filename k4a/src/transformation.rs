@@ -0,0 +1,102 @@
+use super::calibration::{Calibration, CalibrationType};
+use super::error::{k4a_result, Error};
+use super::image::{Image, ImageFormat};
+
+/// Reprojects images between the depth and color cameras.
+pub struct Transformation {
+    transformation_handle: libk4a_sys::k4a_transformation_t,
+    depth_size: (i32, i32),
+    color_size: (i32, i32),
+}
+
+// The handle is not bound to the thread that created it. Every call writes to scratch
+// buffers of the handle, so it must not be `Sync`.
+unsafe impl Send for Transformation {}
+
+impl Transformation {
+    pub fn create(calibration: &Calibration) -> Result<Self, Error> {
+        let transformation_handle = unsafe { libk4a_sys::k4a_transformation_create(calibration.as_ptr()) };
+        if transformation_handle.is_null() {
+            return Err(Error::new("k4a_transformation_create", String::new()));
+        }
+        let depth = &calibration.depth_camera_calibration;
+        let color = &calibration.color_camera_calibration;
+        Ok(Transformation {
+            transformation_handle,
+            depth_size: (depth.resolution_width, depth.resolution_height),
+            color_size: (color.resolution_width, color.resolution_height),
+        })
+    }
+
+    /// Reprojects a DEPTH16 image into the geometry of the color camera.
+    pub fn depth_image_to_color_camera(&self, depth_image: &Image) -> Result<Image, Error> {
+        let (width, height) = self.color_size;
+        let transformed = Image::create(ImageFormat::K4A_IMAGE_FORMAT_DEPTH16, width, height, width * 2)?;
+        let result = unsafe {
+            libk4a_sys::k4a_transformation_depth_image_to_color_camera(
+                self.transformation_handle,
+                **depth_image,
+                *transformed,
+            )
+        };
+        k4a_result(result, "k4a_transformation_depth_image_to_color_camera", String::new)?;
+        Ok(transformed)
+    }
+
+    /// Reprojects a BGRA32 color image into the geometry of the depth camera.
+    pub fn color_image_to_depth_camera(
+        &self,
+        depth_image: &Image,
+        color_image: &Image,
+    ) -> Result<Image, Error> {
+        let (width, height) = self.depth_size;
+        let transformed = Image::create(ImageFormat::K4A_IMAGE_FORMAT_COLOR_BGRA32, width, height, width * 4)?;
+        let result = unsafe {
+            libk4a_sys::k4a_transformation_color_image_to_depth_camera(
+                self.transformation_handle,
+                **depth_image,
+                **color_image,
+                *transformed,
+            )
+        };
+        k4a_result(result, "k4a_transformation_color_image_to_depth_camera", String::new)?;
+        Ok(transformed)
+    }
+
+    /// Produces a CUSTOM image of x, y, z `i16` triplets in millimeters. `camera` is the
+    /// camera whose geometry `depth_image` is in, depth or color.
+    pub fn depth_image_to_point_cloud(
+        &self,
+        depth_image: &Image,
+        camera: CalibrationType,
+    ) -> Result<Image, Error> {
+        let width = depth_image.get_width_pixels();
+        let height = depth_image.get_height_pixels();
+        let xyz_image = Image::create(ImageFormat::K4A_IMAGE_FORMAT_CUSTOM, width, height, width * 6)?;
+        let result = unsafe {
+            libk4a_sys::k4a_transformation_depth_image_to_point_cloud(
+                self.transformation_handle,
+                **depth_image,
+                camera,
+                *xyz_image,
+            )
+        };
+        k4a_result(result, "k4a_transformation_depth_image_to_point_cloud", || {
+            format!("camera: {:?}", camera)
+        })?;
+        Ok(xyz_image)
+    }
+}
+
+impl Drop for Transformation {
+    fn drop(&mut self) {
+        let transformation_handle = self.transformation_handle;
+        if transformation_handle.is_null() {
+            return;
+        }
+        unsafe {
+            libk4a_sys::k4a_transformation_destroy(transformation_handle);
+        }
+        self.transformation_handle = std::ptr::null_mut();
+    }
+}
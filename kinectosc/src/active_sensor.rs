@@ -1,81 +1,225 @@
-use k4a::{Playback, Capture, StreamError, RunningDevice, WaitError, Calibration};
+#[cfg(feature = "sensor")]
+use k4a::{Playback, Capture, StreamError, RunningDevice, WaitError, Calibration, ImuSample, SeekOrigin, Recorder};
+use std::{fmt, io};
+#[cfg(feature = "sensor")]
 use std::time;
-use std::cell::Cell;
+#[cfg(feature = "sensor")]
+use std::cell::{Cell, RefCell};
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug)]
 pub enum Error {
     End,
     Timeout,
-    Fatal,
+    Disconnected,
+    Fatal(Box<dyn std::error::Error + Send + Sync>),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::End => write!(f, "end of stream"),
+            Error::Timeout => write!(f, "timed out"),
+            Error::Disconnected => write!(f, "sensor is disconnected"),
+            Error::Fatal(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "sensor")]
 impl From<k4a::Error> for Error {
     fn from(k4a_err: k4a::Error) -> Self {
-        match k4a_err {
-            k4a::Error::Failed => Error::Fatal,
-        }
+        Error::Fatal(Box::new(k4a_err))
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(io_err: io::Error) -> Self {
+        Error::Fatal(Box::new(io_err))
+    }
+}
+
+#[cfg(feature = "sensor")]
 impl From<WaitError> for Error {
     fn from(wait_err: WaitError) -> Self {
         match wait_err {
             WaitError::Timeout => Error::Timeout,
-            WaitError::Failed => Error::Fatal,
+            WaitError::Failed(e) => e.into(),
         }
     }
 }
 
+#[cfg(feature = "sensor")]
 impl From<StreamError> for Error {
     fn from(stream_err: StreamError) -> Self {
         match stream_err {
-            StreamError::Failed => Error::Fatal,
+            StreamError::Failed(e) => e.into(),
             StreamError::Eof => Error::End,
         }    
     }
 }
 
-pub trait ActiveSensor {
+#[cfg(feature = "sensor")]
+pub trait ActiveSensor: Send {
     fn get_capture(&self, timeout: i32) -> Result<Capture, Error>;
     fn get_calibration(&self) -> Result<Calibration, k4a::Error>;
+    fn get_imu_sample(&self, timeout: i32) -> Result<ImuSample, Error>;
 }
 
+/// Plays a recording back at the pace it was recorded at, only between `start` and
+/// `end` (offsets from the beginning of the recording), and over again if `looping`.
+#[cfg(feature = "sensor")]
 pub struct RealtimePlayback {
     playback: Playback,
-    interval: time::Duration,
-    last_time: Cell<time::Instant>,
+    start: time::Duration,
+    end: Option<time::Duration>,
+    looping: bool,
+    first_timestamp_usec: u64,
+    // wall clock time and device timestamp of the first capture since the last seek
+    anchor: Cell<Option<(time::Instant, u64)>>,
 }
 
+#[cfg(feature = "sensor")]
 impl RealtimePlayback {
-    pub fn new(playback: Playback, fps: u32) -> Self {
-        let last_time = Cell::new(time::Instant::now());
-        let interval = time::Duration::from_secs(1) / fps;
-        RealtimePlayback {
+    pub fn new(
+        playback: Playback,
+        start: time::Duration,
+        end: Option<time::Duration>,
+        looping: bool,
+    ) -> Result<Self, k4a::Error> {
+        let record_configuration = playback.get_record_configuration()?;
+        let realtime_playback = RealtimePlayback {
             playback,
-            interval,
-            last_time,
+            start,
+            end,
+            looping,
+            first_timestamp_usec: record_configuration.start_timestamp_offset_usec as u64,
+            anchor: Cell::new(None),
+        };
+        realtime_playback.rewind()?;
+        Ok(realtime_playback)
+    }
+
+    fn rewind(&self) -> Result<(), k4a::Error> {
+        self.playback.seek_timestamp(self.start.as_micros() as i64, SeekOrigin::K4A_PLAYBACK_SEEK_BEGIN)?;
+        self.anchor.set(None);
+        Ok(())
+    }
+
+    fn is_past_end(&self, capture: &Capture) -> bool {
+        match (self.end, capture.get_device_timestamp_usec()) {
+            (Some(end), Some(timestamp)) => {
+                timestamp.saturating_sub(self.first_timestamp_usec) >= end.as_micros() as u64
+            }
+            _ => false,
         }
     }
 }
 
+#[cfg(feature = "sensor")]
 impl ActiveSensor for RealtimePlayback {
     fn get_capture(&self, _timeout: i32) -> Result<Capture, Error> {
-        let start_time = time::Instant::now();
-        let last_time = self.last_time.get();
-        let elapsed = start_time - last_time;
-        if self.interval > elapsed {
-            std::thread::sleep(self.interval - elapsed);
+        let capture = match self.playback.get_capture() {
+            Ok(capture) if !self.is_past_end(&capture) => capture,
+            Ok(_) | Err(StreamError::Eof) if self.looping => {
+                self.rewind()?;
+                self.playback.get_capture()?
+            }
+            Ok(_) => return Err(Error::End),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(timestamp) = capture.get_device_timestamp_usec() {
+            let (start_time, start_timestamp) = match self.anchor.get() {
+                Some(anchor) => anchor,
+                None => {
+                    let anchor = (time::Instant::now(), timestamp);
+                    self.anchor.set(Some(anchor));
+                    anchor
+                }
+            };
+            let due = start_time + time::Duration::from_micros(timestamp.saturating_sub(start_timestamp));
+            let now = time::Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
         }
-        let r = ActiveSensor::get_capture(&self.playback, 0);
-        self.last_time.set(time::Instant::now());
-        r
+        Ok(capture)
     }
     
     fn get_calibration(&self) -> Result<Calibration, k4a::Error> {
         ActiveSensor::get_calibration(&self.playback)
     }
+
+    fn get_imu_sample(&self, timeout: i32) -> Result<ImuSample, Error> {
+        ActiveSensor::get_imu_sample(&self.playback, timeout)
+    }
+}
+
+/// Writes every capture and IMU sample read from `sensor` to a recording. Stops
+/// recording, but keeps reading, if writing fails.
+#[cfg(feature = "sensor")]
+pub struct RecordingSensor {
+    // closed before the sensor is stopped
+    recorder: RefCell<Option<Recorder>>,
+    sensor: Box<dyn ActiveSensor>,
 }
 
+#[cfg(feature = "sensor")]
+impl RecordingSensor {
+    /// `recorder` must have its header written already.
+    pub fn new(sensor: Box<dyn ActiveSensor>, recorder: Recorder) -> Self {
+        RecordingSensor {
+            recorder: RefCell::new(Some(recorder)),
+            sensor,
+        }
+    }
+
+    fn record<F>(&self, write: F)
+    where
+        F: FnOnce(&Recorder) -> Result<(), k4a::Error>,
+    {
+        let mut recorder = self.recorder.borrow_mut();
+        if let Some(r) = recorder.as_ref() {
+            if let Err(e) = write(r) {
+                eprintln!("Could not write to recording, recording stopped: {}", e);
+                *recorder = None;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sensor")]
+impl ActiveSensor for RecordingSensor {
+    fn get_capture(&self, timeout: i32) -> Result<Capture, Error> {
+        let capture = self.sensor.get_capture(timeout)?;
+        self.record(|recorder| recorder.write_capture(&capture));
+        Ok(capture)
+    }
+
+    fn get_calibration(&self) -> Result<Calibration, k4a::Error> {
+        self.sensor.get_calibration()
+    }
+
+    fn get_imu_sample(&self, timeout: i32) -> Result<ImuSample, Error> {
+        let imu_sample = self.sensor.get_imu_sample(timeout)?;
+        self.record(|recorder| recorder.write_imu_sample(&imu_sample));
+        Ok(imu_sample)
+    }
+}
+
+#[cfg(feature = "sensor")]
+impl Drop for RecordingSensor {
+    fn drop(&mut self) {
+        if let Some(recorder) = self.recorder.get_mut().take() {
+            if let Err(e) = recorder.close() {
+                eprintln!("Could not finish recording: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sensor")]
 impl ActiveSensor for Playback {
     fn get_capture(&self, _timeout: i32) -> Result<Capture, Error> {
         Ok(self.get_capture()?)
@@ -84,8 +228,13 @@ impl ActiveSensor for Playback {
     fn get_calibration(&self) -> Result<Calibration, k4a::Error> {
         self.get_calibration()
     }    
+
+    fn get_imu_sample(&self, _timeout: i32) -> Result<ImuSample, Error> {
+        Ok(self.get_next_imu_sample()?)
+    }
 }
 
+#[cfg(feature = "sensor")]
 impl ActiveSensor for RunningDevice {
     fn get_capture(&self, timeout: i32) -> Result<Capture, Error> {
         Ok(self.get_capture(timeout)?)
@@ -94,4 +243,8 @@ impl ActiveSensor for RunningDevice {
     fn get_calibration(&self) -> Result<Calibration, k4a::Error> {
         self.get_calibration()
     }
+
+    fn get_imu_sample(&self, timeout: i32) -> Result<ImuSample, Error> {
+        Ok(self.get_imu_sample(timeout)?)
+    }
 }
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Spaces out retries of something that keeps failing, doubling the delay after every
+/// failure up to a limit.
+pub struct Backoff {
+    delay: Duration,
+    retry_at: Instant,
+}
+
+impl Backoff {
+    /// Due right away.
+    pub fn new() -> Self {
+        Backoff {
+            delay: INITIAL_DELAY,
+            retry_at: Instant::now(),
+        }
+    }
+
+    /// How long the next retry waits after a failure.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.retry_at
+    }
+
+    /// How long until a retry is due, zero once it is.
+    pub fn remaining(&self) -> Duration {
+        self.retry_at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn fail(&mut self) {
+        self.retry_at = Instant::now() + self.delay;
+        self.delay = (self.delay * 2).min(MAX_DELAY);
+    }
+
+    pub fn succeed(&mut self) {
+        self.delay = INITIAL_DELAY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new();
+        assert!(backoff.is_due());
+        backoff.fail();
+        assert!(!backoff.is_due());
+        assert!(backoff.remaining() > Duration::from_millis(400));
+        assert_eq!(Duration::from_secs(1), backoff.delay());
+        for _ in 0..10 {
+            backoff.fail();
+        }
+        assert_eq!(MAX_DELAY, backoff.delay());
+        backoff.succeed();
+        assert_eq!(INITIAL_DELAY, backoff.delay());
+    }
+}
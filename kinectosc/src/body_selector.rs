@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use k4a::joint_id;
+use nalgebra::Point3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionPolicy {
+    FirstSeen,
+    ClosestToSensor,
+    ClosestToPoint(Point3<f64>),
+    Locked(u32),
+}
+
+pub struct BodySelector {
+    policy: SelectionPolicy,
+    slots: Vec<Option<u32>>,
+    first_seen: HashMap<u32, u64>,
+    seen_count: u64,
+}
+
+impl BodySelector {
+    pub fn new(policy: SelectionPolicy, max_bodies: usize) -> Self {
+        Self {
+            policy,
+            slots: vec![None; max_bodies],
+            first_seen: HashMap::new(),
+            seen_count: 0,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: SelectionPolicy) {
+        self.policy = policy;
+    }
+
+    /// Assigns bodies to output slots, keeping a body in its slot for as long as it stays
+    /// selected. Returns an index into `bodies` for each slot.
+    pub fn select(&mut self, bodies: &[k4a::Body]) -> Vec<Option<usize>> {
+        self.first_seen.retain(|id, _| bodies.iter().any(|body| body.id == *id));
+        for body in bodies {
+            let seen_count = &mut self.seen_count;
+            self.first_seen.entry(body.id).or_insert_with(|| {
+                *seen_count += 1;
+                *seen_count
+            });
+        }
+
+        let mut ranked: Vec<usize> = (0..bodies.len()).collect();
+        let first_seen = &self.first_seen;
+        let by_first_seen = |idx: &usize| first_seen[&bodies[*idx].id];
+        match &self.policy {
+            SelectionPolicy::FirstSeen => {
+                ranked.sort_by_key(by_first_seen);
+            }
+            SelectionPolicy::ClosestToSensor => {
+                sort_by_distance(&mut ranked, bodies, &Point3::origin());
+            }
+            SelectionPolicy::ClosestToPoint(point) => {
+                sort_by_distance(&mut ranked, bodies, point);
+            }
+            SelectionPolicy::Locked(locked_id) => {
+                ranked.sort_by_key(|idx| (bodies[*idx].id != *locked_id, by_first_seen(idx)));
+            }
+        }
+        ranked.truncate(self.slots.len());
+
+        for slot in self.slots.iter_mut() {
+            let keep = slot.is_some_and(|id| ranked.iter().any(|idx| bodies[*idx].id == id));
+            if !keep {
+                *slot = None;
+            }
+        }
+        for idx in ranked {
+            let id = bodies[idx].id;
+            if self.slots.contains(&Some(id)) {
+                continue;
+            }
+            if let Some(slot) = self.slots.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(id);
+            }
+        }
+
+        self.slots
+            .iter()
+            .map(|slot| slot.and_then(|id| bodies.iter().position(|body| body.id == id)))
+            .collect()
+    }
+}
+
+fn sort_by_distance(ranked: &mut [usize], bodies: &[k4a::Body], point: &Point3<f64>) {
+    let distance = |idx: &usize| {
+        let pelvis = &bodies[*idx].skeleton.joints[joint_id::K4ABT_JOINT_PELVIS as usize].position;
+        nalgebra::distance(&Point3::new(pelvis.x as f64, pelvis.y as f64, pelvis.z as f64), point)
+    };
+    ranked.sort_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeleton_source::{joint_at, test_body};
+
+    fn body(id: u32, z: f32) -> k4a::Body {
+        test_body(id, |_| joint_at(0., 0., z))
+    }
+
+    fn selected_ids(selector: &mut BodySelector, bodies: &[k4a::Body]) -> Vec<Option<u32>> {
+        selector
+            .select(bodies)
+            .into_iter()
+            .map(|idx| idx.map(|idx| bodies[idx].id))
+            .collect()
+    }
+
+    #[test]
+    fn test_slots_are_sticky() {
+        let mut selector = BodySelector::new(SelectionPolicy::FirstSeen, 2);
+        assert_eq!(vec![Some(3), None], selected_ids(&mut selector, &[body(3, 2000.)]));
+        assert_eq!(
+            vec![Some(3), Some(1)],
+            selected_ids(&mut selector, &[body(1, 1000.), body(3, 2000.)])
+        );
+        assert_eq!(vec![None, Some(1)], selected_ids(&mut selector, &[body(1, 1000.)]));
+    }
+
+    #[test]
+    fn test_policies() {
+        let bodies = [body(1, 3000.), body(2, 1000.), body(3, 2000.)];
+        let mut selector = BodySelector::new(SelectionPolicy::FirstSeen, 1);
+        assert_eq!(vec![Some(1)], selected_ids(&mut selector, &bodies));
+
+        let mut selector = BodySelector::new(SelectionPolicy::ClosestToSensor, 1);
+        assert_eq!(vec![Some(2)], selected_ids(&mut selector, &bodies));
+
+        let point = Point3::new(0., 0., 2100.);
+        let mut selector = BodySelector::new(SelectionPolicy::ClosestToPoint(point), 1);
+        assert_eq!(vec![Some(3)], selected_ids(&mut selector, &bodies));
+
+        selector.set_policy(SelectionPolicy::Locked(1));
+        assert_eq!(vec![Some(1)], selected_ids(&mut selector, &bodies));
+    }
+}
@@ -0,0 +1,765 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use nalgebra::{Isometry3, Point3, Quaternion, Translation3, UnitQuaternion};
+use serde::{Deserialize, Serialize};
+
+use super::body_selector::SelectionPolicy;
+use super::filter::SmoothParams;
+use super::transport;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            Error::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            Error::Invalid(msg) => write!(f, "invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub osc: OscConfig,
+    /// Where each processed frame goes.
+    pub outputs: Vec<Output>,
+    pub sensor: SensorConfig,
+    pub tracker: TrackerConfig,
+    pub profile: PathBuf,
+    pub joint_map: PathBuf,
+    pub smoothing: SmoothParams,
+    pub bodies: BodiesConfig,
+    pub fusion: FusionConfig,
+    pub metrics: MetricsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            osc: OscConfig::default(),
+            outputs: vec![Output::Osc],
+            sensor: SensorConfig::default(),
+            tracker: TrackerConfig::default(),
+            profile: "calibration_profile.json".into(),
+            joint_map: "joint_map.json".into(),
+            smoothing: SmoothParams::default(),
+            bodies: BodiesConfig::default(),
+            fusion: FusionConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OscConfig {
+    pub bind: SocketAddr,
+    pub target: SocketAddr,
+    /// Send to all of these instead of `target`.
+    pub destinations: Vec<Destination>,
+    /// Send the messages of each frame in one bundle, timetagged with when the frame
+    /// was captured, instead of one datagram per message.
+    pub bundle: bool,
+    /// Accept control messages, such as `/kinectosc/pause`, on the bind address. Off by
+    /// default, since anyone who can reach that address could send them.
+    pub control: bool,
+    pub format: OscFormat,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            bind: ([0, 0, 0, 0], 9010).into(),
+            target: ([127, 0, 0, 1], 8124).into(),
+            destinations: Vec::new(),
+            bundle: false,
+            control: false,
+            format: OscFormat::Tracker,
+        }
+    }
+}
+
+impl OscConfig {
+    pub fn destinations(&self) -> Vec<transport::Destination> {
+        if self.destinations.is_empty() {
+            return vec![transport::Destination::Udp(self.target)];
+        }
+        self.destinations.iter().map(|destination| destination.into()).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "sink", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Output {
+    /// Sends to the destinations of the `osc` section, in its format.
+    Osc,
+    /// Appends the tracker poses of each frame to a file, one JSON object per line.
+    File { path: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OscFormat {
+    /// `/Tracker/Pose` and `/Tracker/NoBody` per tracker.
+    Tracker,
+    /// Virtual Motion Capture bone and tracker messages, driving an avatar after the
+    /// first selected body. Always sent as one bundle per frame.
+    Vmc,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "transport", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Destination {
+    Udp {
+        address: SocketAddr,
+    },
+    Tcp {
+        address: SocketAddr,
+        #[serde(default)]
+        framing: Framing,
+    },
+    Unix {
+        path: PathBuf,
+    },
+}
+
+impl From<&Destination> for transport::Destination {
+    fn from(destination: &Destination) -> Self {
+        match destination {
+            Destination::Udp { address } => transport::Destination::Udp(*address),
+            Destination::Tcp { address, framing } => transport::Destination::Tcp(*address, (*framing).into()),
+            Destination::Unix { path } => transport::Destination::Unix(path.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    #[default]
+    Slip,
+    LengthPrefix,
+}
+
+impl From<Framing> for transport::Framing {
+    fn from(framing: Framing) -> Self {
+        match framing {
+            Framing::Slip => transport::Framing::Slip,
+            Framing::LengthPrefix => transport::Framing::LengthPrefix,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorConfig {
+    pub device_index: u32,
+    /// Open the device with this serial number instead of `device_index`.
+    pub serial: Option<String>,
+    pub depth_mode: DepthMode,
+    /// Rotate skeletons so that they stand upright however the sensor is tilted,
+    /// using the accelerometer.
+    pub level_with_gravity: bool,
+    /// Applied to the color camera on startup, see `kinectosc color-controls`.
+    pub color_controls: ColorControls,
+    /// Track with all of these devices and fuse their skeletons, instead of opening
+    /// `device_index` alone.
+    pub devices: Vec<DeviceConfig>,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            device_index: 0,
+            serial: None,
+            depth_mode: DepthMode::NfovUnbinned,
+            level_with_gravity: false,
+            color_controls: ColorControls::new(),
+            devices: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "sensor")]
+impl SensorConfig {
+    pub fn device_configuration(&self) -> k4a::DeviceConfiguration {
+        k4a::DeviceConfiguration {
+            depth_mode: self.depth_mode.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub serial: String,
+    #[serde(default)]
+    pub sync: SyncRole,
+    /// Stagger this between devices, by at least 160us, so that their depth lasers do
+    /// not interfere.
+    #[serde(default)]
+    pub depth_delay_off_color_usec: i32,
+    /// Only for subordinates.
+    #[serde(default)]
+    pub subordinate_delay_off_master_usec: u32,
+    /// Pose of the device in the shared space, applied after leveling.
+    #[serde(default)]
+    pub extrinsic: Extrinsic,
+    /// Override `sensor.color_controls` for this device.
+    #[serde(default)]
+    pub color_controls: ColorControls,
+}
+
+impl DeviceConfig {
+    #[cfg(feature = "sensor")]
+    pub fn device_configuration(&self, sensor: &SensorConfig) -> k4a::DeviceConfiguration {
+        // the SDK does not start a master without its color camera, and synced devices
+        // time their depth cameras off the color camera
+        let color_resolution = match self.sync {
+            SyncRole::Standalone => k4a::ColorResolution::K4A_COLOR_RESOLUTION_OFF,
+            SyncRole::Master | SyncRole::Subordinate => k4a::ColorResolution::K4A_COLOR_RESOLUTION_720P,
+        };
+        k4a::DeviceConfiguration {
+            color_resolution,
+            wired_sync_mode: self.sync.into(),
+            depth_delay_off_color_usec: self.depth_delay_off_color_usec,
+            subordinate_delay_off_master_usec: self.subordinate_delay_off_master_usec,
+            ..sensor.device_configuration()
+        }
+    }
+
+    pub fn color_controls(&self, sensor: &SensorConfig) -> ColorControls {
+        let mut color_controls = sensor.color_controls.clone();
+        color_controls.extend(self.color_controls.iter().map(|(control, setting)| (*control, *setting)));
+        color_controls
+    }
+}
+
+pub type ColorControls = BTreeMap<ColorControl, ColorControlSetting>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorControl {
+    ExposureTimeUsec,
+    AutoExposurePriority,
+    Brightness,
+    Contrast,
+    Saturation,
+    Sharpness,
+    WhiteBalance,
+    BacklightCompensation,
+    Gain,
+    /// 1 for 50Hz, 2 for 60Hz.
+    PowerlineFrequency,
+}
+
+impl ColorControl {
+    pub const ALL: [ColorControl; 10] = [
+        ColorControl::ExposureTimeUsec,
+        ColorControl::AutoExposurePriority,
+        ColorControl::Brightness,
+        ColorControl::Contrast,
+        ColorControl::Saturation,
+        ColorControl::Sharpness,
+        ColorControl::WhiteBalance,
+        ColorControl::BacklightCompensation,
+        ColorControl::Gain,
+        ColorControl::PowerlineFrequency,
+    ];
+}
+
+#[cfg(feature = "sensor")]
+impl From<ColorControl> for k4a::ColorControlCommand {
+    fn from(control: ColorControl) -> Self {
+        match control {
+            ColorControl::ExposureTimeUsec => k4a::ColorControlCommand::K4A_COLOR_CONTROL_EXPOSURE_TIME_ABSOLUTE,
+            ColorControl::AutoExposurePriority => k4a::ColorControlCommand::K4A_COLOR_CONTROL_AUTO_EXPOSURE_PRIORITY,
+            ColorControl::Brightness => k4a::ColorControlCommand::K4A_COLOR_CONTROL_BRIGHTNESS,
+            ColorControl::Contrast => k4a::ColorControlCommand::K4A_COLOR_CONTROL_CONTRAST,
+            ColorControl::Saturation => k4a::ColorControlCommand::K4A_COLOR_CONTROL_SATURATION,
+            ColorControl::Sharpness => k4a::ColorControlCommand::K4A_COLOR_CONTROL_SHARPNESS,
+            ColorControl::WhiteBalance => k4a::ColorControlCommand::K4A_COLOR_CONTROL_WHITEBALANCE,
+            ColorControl::BacklightCompensation => k4a::ColorControlCommand::K4A_COLOR_CONTROL_BACKLIGHT_COMPENSATION,
+            ColorControl::Gain => k4a::ColorControlCommand::K4A_COLOR_CONTROL_GAIN,
+            ColorControl::PowerlineFrequency => k4a::ColorControlCommand::K4A_COLOR_CONTROL_POWERLINE_FREQUENCY,
+        }
+    }
+}
+
+/// `"auto"`, or a fixed value.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColorControlSetting {
+    Manual(i32),
+    Auto(Auto),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Auto {
+    Auto,
+}
+
+impl ColorControlSetting {
+    #[cfg(feature = "sensor")]
+    pub fn from_k4a(mode: k4a::ColorControlMode, value: i32) -> Self {
+        if mode == k4a::ColorControlMode::K4A_COLOR_CONTROL_MODE_AUTO {
+            ColorControlSetting::Auto(Auto::Auto)
+        } else {
+            ColorControlSetting::Manual(value)
+        }
+    }
+
+    #[cfg(feature = "sensor")]
+    pub fn to_k4a(self) -> (k4a::ColorControlMode, i32) {
+        match self {
+            ColorControlSetting::Manual(value) => (k4a::ColorControlMode::K4A_COLOR_CONTROL_MODE_MANUAL, value),
+            ColorControlSetting::Auto(_) => (k4a::ColorControlMode::K4A_COLOR_CONTROL_MODE_AUTO, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncRole {
+    #[default]
+    Standalone,
+    Master,
+    Subordinate,
+}
+
+#[cfg(feature = "sensor")]
+impl From<SyncRole> for k4a::WiredSyncMode {
+    fn from(sync: SyncRole) -> Self {
+        match sync {
+            SyncRole::Standalone => k4a::WiredSyncMode::K4A_WIRED_SYNC_MODE_STANDALONE,
+            SyncRole::Master => k4a::WiredSyncMode::K4A_WIRED_SYNC_MODE_MASTER,
+            SyncRole::Subordinate => k4a::WiredSyncMode::K4A_WIRED_SYNC_MODE_SUBORDINATE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Extrinsic {
+    /// In millimeters, like skeleton joint positions.
+    pub translation: [f64; 3],
+    /// w, x, y, z
+    pub rotation: [f64; 4],
+}
+
+impl Default for Extrinsic {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl Extrinsic {
+    pub fn isometry(&self) -> Isometry3<f64> {
+        let [x, y, z] = self.translation;
+        let [w, i, j, k] = self.rotation;
+        Isometry3::from_parts(
+            Translation3::new(x, y, z),
+            UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthMode {
+    #[serde(rename = "nfov_2x2binned")]
+    Nfov2x2Binned,
+    NfovUnbinned,
+    #[serde(rename = "wfov_2x2binned")]
+    Wfov2x2Binned,
+    WfovUnbinned,
+}
+
+#[cfg(feature = "sensor")]
+impl From<DepthMode> for k4a::DepthMode {
+    fn from(depth_mode: DepthMode) -> Self {
+        match depth_mode {
+            DepthMode::Nfov2x2Binned => k4a::DepthMode::K4A_DEPTH_MODE_NFOV_2X2BINNED,
+            DepthMode::NfovUnbinned => k4a::DepthMode::K4A_DEPTH_MODE_NFOV_UNBINNED,
+            DepthMode::Wfov2x2Binned => k4a::DepthMode::K4A_DEPTH_MODE_WFOV_2X2BINNED,
+            DepthMode::WfovUnbinned => k4a::DepthMode::K4A_DEPTH_MODE_WFOV_UNBINNED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackerConfig {
+    pub processing_mode: ProcessingMode,
+    /// Ignored in CPU mode.
+    pub gpu_device_id: i32,
+    pub sensor_orientation: SensorOrientation,
+    /// The ONNX model file to track with instead of the SDK's full model, such as the
+    /// faster but less accurate lite model `dnn_model_2_0_lite_op11.onnx` that comes with
+    /// the SDK. Relative paths are resolved against the working directory.
+    pub model_path: Option<PathBuf>,
+    /// From 0 (off) to 1, `None` keeps the SDK's default.
+    pub temporal_smoothing: Option<f32>,
+    /// Read captures while the tracker runs inference, see `pipeline::Pipeline`.
+    pub pipelined: bool,
+    /// Captures buffered in pipelined mode before the oldest are dropped.
+    pub queue_depth: usize,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            processing_mode: ProcessingMode::Gpu,
+            gpu_device_id: 1,
+            sensor_orientation: SensorOrientation::Default,
+            model_path: None,
+            temporal_smoothing: None,
+            pipelined: false,
+            queue_depth: 2,
+        }
+    }
+}
+
+impl TrackerConfig {
+    pub fn pipeline_depth(&self) -> Option<usize> {
+        if self.pipelined {
+            Some(self.queue_depth)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "sensor")]
+    pub fn tracker_configuration(&self) -> k4a::TrackerConfiguration {
+        k4a::TrackerConfiguration {
+            sensor_orientation: self.sensor_orientation.into(),
+            processing_mode: self.processing_mode.into(),
+            gpu_device_id: self.gpu_device_id,
+            model_path: self.model_path.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingMode {
+    /// The SDK's default GPU backend for the platform.
+    Gpu,
+    Cpu,
+    Cuda,
+    #[serde(rename = "tensorrt")]
+    TensorRt,
+    #[serde(rename = "directml")]
+    DirectMl,
+}
+
+#[cfg(feature = "sensor")]
+impl From<ProcessingMode> for k4a::TrackerProcessingMode {
+    fn from(processing_mode: ProcessingMode) -> Self {
+        match processing_mode {
+            ProcessingMode::Gpu => k4a::TrackerProcessingMode::K4ABT_TRACKER_PROCESSING_MODE_GPU,
+            ProcessingMode::Cpu => k4a::TrackerProcessingMode::K4ABT_TRACKER_PROCESSING_MODE_CPU,
+            ProcessingMode::Cuda => k4a::TrackerProcessingMode::K4ABT_TRACKER_PROCESSING_MODE_GPU_CUDA,
+            ProcessingMode::TensorRt => k4a::TrackerProcessingMode::K4ABT_TRACKER_PROCESSING_MODE_GPU_TENSORRT,
+            ProcessingMode::DirectMl => k4a::TrackerProcessingMode::K4ABT_TRACKER_PROCESSING_MODE_GPU_DIRECTML,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorOrientation {
+    Default,
+    #[serde(rename = "clockwise90")]
+    Clockwise90,
+    #[serde(rename = "counterclockwise90")]
+    CounterClockwise90,
+    #[serde(rename = "flip180")]
+    Flip180,
+}
+
+#[cfg(feature = "sensor")]
+impl From<SensorOrientation> for k4a::SensorOrientation {
+    fn from(sensor_orientation: SensorOrientation) -> Self {
+        match sensor_orientation {
+            SensorOrientation::Default => k4a::SensorOrientation::K4ABT_SENSOR_ORIENTATION_DEFAULT,
+            SensorOrientation::Clockwise90 => k4a::SensorOrientation::K4ABT_SENSOR_ORIENTATION_CLOCKWISE90,
+            SensorOrientation::CounterClockwise90 => {
+                k4a::SensorOrientation::K4ABT_SENSOR_ORIENTATION_COUNTERCLOCKWISE90
+            }
+            SensorOrientation::Flip180 => k4a::SensorOrientation::K4ABT_SENSOR_ORIENTATION_FLIP180,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BodiesConfig {
+    pub max: usize,
+    pub selection: Selection,
+}
+
+impl Default for BodiesConfig {
+    fn default() -> Self {
+        Self {
+            max: 1,
+            selection: Selection::FirstSeen,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Selection {
+    FirstSeen,
+    ClosestToSensor,
+    ClosestToPoint { point: [f64; 3] },
+    Locked { id: u32 },
+}
+
+impl From<&Selection> for SelectionPolicy {
+    fn from(selection: &Selection) -> Self {
+        match selection {
+            Selection::FirstSeen => SelectionPolicy::FirstSeen,
+            Selection::ClosestToSensor => SelectionPolicy::ClosestToSensor,
+            Selection::ClosestToPoint { point: [x, y, z] } => {
+                SelectionPolicy::ClosestToPoint(Point3::new(*x, *y, *z))
+            }
+            Selection::Locked { id } => SelectionPolicy::Locked(*id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FusionConfig {
+    /// Bodies seen by different devices whose pelvises are closer than this, in
+    /// millimeters, are taken to be the same person.
+    pub match_distance_mm: f64,
+    /// How long to wait for the other devices once one of them has a result.
+    pub window_ms: u64,
+}
+
+impl Default for FusionConfig {
+    fn default() -> Self {
+        Self {
+            match_distance_mm: 300.0,
+            window_ms: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Log frame timing, with the pipeline stages when pipelined, every this many seconds,
+    /// 0 to never log it.
+    pub log_interval_secs: u64,
+    /// Serve frame timing as JSON over HTTP on this address.
+    pub listen: Option<SocketAddr>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|e| Error::Io(path.into(), e))?;
+        let config: Config = serde_json::from_reader(io::BufReader::new(file))
+            .map_err(|e| Error::Parse(path.into(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.outputs.iter().filter(|output| **output == Output::Osc).count() > 1 {
+            return Err(Error::Invalid("outputs may have only one osc sink".into()));
+        }
+        if !cfg!(unix) && self.osc.destinations.iter().any(|d| matches!(d, Destination::Unix { .. })) {
+            return Err(Error::Invalid("osc.destinations: unix sockets are not supported on this platform".into()));
+        }
+        if self.tracker.gpu_device_id < 0 {
+            return Err(Error::Invalid(format!(
+                "tracker.gpu_device_id must not be negative, got {}",
+                self.tracker.gpu_device_id
+            )));
+        }
+        if let Some(smoothing) = self.tracker.temporal_smoothing {
+            if !(0.0..=1.0).contains(&smoothing) {
+                return Err(Error::Invalid(format!(
+                    "tracker.temporal_smoothing must be between 0 and 1, got {}",
+                    smoothing
+                )));
+            }
+        }
+        if self.tracker.queue_depth == 0 {
+            return Err(Error::Invalid("tracker.queue_depth must be at least 1".into()));
+        }
+        let devices = &self.sensor.devices;
+        for (index, device) in devices.iter().enumerate() {
+            if devices[..index].iter().any(|other| other.serial == device.serial) {
+                return Err(Error::Invalid(format!("sensor.devices lists {} twice", device.serial)));
+            }
+            if device.sync != SyncRole::Subordinate && device.subordinate_delay_off_master_usec != 0 {
+                return Err(Error::Invalid(format!(
+                    "sensor.devices: {} is not a subordinate, so it cannot have subordinate_delay_off_master_usec",
+                    device.serial
+                )));
+            }
+            if device.extrinsic.rotation.iter().all(|v| *v == 0.0) {
+                return Err(Error::Invalid(format!(
+                    "sensor.devices: the extrinsic rotation of {} is not a rotation",
+                    device.serial
+                )));
+            }
+        }
+        let color_controls = std::iter::once(&self.sensor.color_controls)
+            .chain(devices.iter().map(|device| &device.color_controls));
+        for (control, setting) in color_controls.flat_map(|color_controls| color_controls.iter()) {
+            let supports_auto = matches!(control, ColorControl::ExposureTimeUsec | ColorControl::WhiteBalance);
+            if let ColorControlSetting::Auto(_) = setting {
+                if !supports_auto {
+                    return Err(Error::Invalid(format!("color control {:?} cannot be auto", control)));
+                }
+            }
+        }
+        if devices.iter().filter(|device| device.sync == SyncRole::Master).count() > 1 {
+            return Err(Error::Invalid("sensor.devices may have only one master".into()));
+        }
+        if self.fusion.match_distance_mm <= 0.0 {
+            return Err(Error::Invalid("fusion.match_distance_mm must be positive".into()));
+        }
+        if self.bodies.max == 0 {
+            return Err(Error::Invalid("bodies.max must be at least 1".into()));
+        }
+        self.smoothing
+            .validate()
+            .map_err(|msg| Error::Invalid(format!("smoothing.{}", msg)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"{
+        "osc": {
+            "target": "192.168.0.10:8124",
+            "format": "vmc",
+            "destinations": [
+                { "transport": "udp", "address": "192.168.0.11:8124" },
+                { "transport": "tcp", "address": "127.0.0.1:9000", "framing": "length_prefix" }
+            ]
+        },
+        "sensor": {
+            "depth_mode": "wfov_2x2binned",
+            "devices": [
+                { "serial": "000123", "sync": "master" },
+                { "serial": "000456", "sync": "subordinate", "depth_delay_off_color_usec": 160,
+                  "extrinsic": { "translation": [1000, 0, 0] },
+                  "color_controls": { "exposure_time_usec": 8330 } }
+            ],
+            "color_controls": { "exposure_time_usec": "auto", "powerline_frequency": 1 }
+        },
+        "tracker": { "processing_mode": "cpu", "sensor_orientation": "clockwise90",
+                     "model_path": "models/dnn_model_2_0_lite_op11.onnx" },
+        "bodies": { "max": 2, "selection": { "policy": "locked", "id": 3 } },
+        "outputs": [{ "sink": "osc" }, { "sink": "file", "path": "poses.jsonl" }]
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let config: Config = serde_json::from_str(EXAMPLE).unwrap();
+        config.validate().unwrap();
+        assert_eq!("192.168.0.10:8124".parse::<SocketAddr>().unwrap(), config.osc.target);
+        assert_eq!(OscConfig::default().bind, config.osc.bind);
+        assert_eq!(OscFormat::Vmc, config.osc.format);
+        assert_eq!(Output::File { path: "poses.jsonl".into() }, config.outputs[1]);
+        match &config.osc.destinations()[..] {
+            [transport::Destination::Udp(_), transport::Destination::Tcp(_, transport::Framing::LengthPrefix)] => {}
+            destinations => panic!("unexpected destinations {:?}", destinations),
+        }
+        assert_eq!(SelectionPolicy::Locked(3), (&config.bodies.selection).into());
+        assert_eq!(1000.0, config.sensor.devices[1].extrinsic.isometry().translation.x);
+        let color_controls = config.sensor.devices[1].color_controls(&config.sensor);
+        assert_eq!(
+            vec![
+                (ColorControl::ExposureTimeUsec, ColorControlSetting::Manual(8330)),
+                (ColorControl::PowerlineFrequency, ColorControlSetting::Manual(1)),
+            ],
+            color_controls.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ColorControlSetting::Auto(Auto::Auto),
+            config.sensor.color_controls[&ColorControl::ExposureTimeUsec]
+        );
+    }
+
+    #[cfg(feature = "sensor")]
+    #[test]
+    fn test_sensor_configuration() {
+        let config: Config = serde_json::from_str(EXAMPLE).unwrap();
+        assert_eq!(
+            k4a::DepthMode::K4A_DEPTH_MODE_WFOV_2X2BINNED,
+            config.sensor.device_configuration().depth_mode
+        );
+        let subordinate = config.sensor.devices[1].device_configuration(&config.sensor);
+        assert_eq!(k4a::WiredSyncMode::K4A_WIRED_SYNC_MODE_SUBORDINATE, subordinate.wired_sync_mode);
+        assert_eq!(k4a::DepthMode::K4A_DEPTH_MODE_WFOV_2X2BINNED, subordinate.depth_mode);
+        assert_eq!(160, subordinate.depth_delay_off_color_usec);
+        let master = config.sensor.devices[0].device_configuration(&config.sensor);
+        assert_eq!(k4a::ColorResolution::K4A_COLOR_RESOLUTION_720P, master.color_resolution);
+        let tracker_configuration = config.tracker.tracker_configuration();
+        assert_eq!(
+            k4a::TrackerProcessingMode::K4ABT_TRACKER_PROCESSING_MODE_CPU,
+            tracker_configuration.processing_mode
+        );
+        assert_eq!(
+            k4a::SensorOrientation::K4ABT_SENSOR_ORIENTATION_CLOCKWISE90,
+            tracker_configuration.sensor_orientation
+        );
+        assert_eq!(
+            Some(PathBuf::from("models/dnn_model_2_0_lite_op11.onnx")),
+            tracker_configuration.model_path
+        );
+    }
+
+    #[test]
+    fn test_reject_invalid() {
+        assert!(serde_json::from_str::<Config>(r#"{ "osc": { "taget": "127.0.0.1:8124" } }"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{ "osc": { "target": "localhost" } }"#).is_err());
+        let config: Config = serde_json::from_str(r#"{ "smoothing": { "prediction": 1.5 } }"#).unwrap();
+        assert!(config.validate().is_err());
+        let config: Config = serde_json::from_str(r#"{ "tracker": { "temporal_smoothing": 2 } }"#).unwrap();
+        assert!(config.validate().is_err());
+        let config: Config = serde_json::from_str(
+            r#"{ "sensor": { "devices": [{ "serial": "000123" }, { "serial": "000123" }] } }"#,
+        ).unwrap();
+        assert!(config.validate().is_err());
+        let config: Config = serde_json::from_str(
+            r#"{ "sensor": { "devices": [{ "serial": "000123", "sync": "master", "subordinate_delay_off_master_usec": 100 }] } }"#,
+        ).unwrap();
+        assert!(config.validate().is_err());
+        let config: Config = serde_json::from_str(r#"{ "sensor": { "color_controls": { "gain": "auto" } } }"#).unwrap();
+        assert!(config.validate().is_err());
+        assert!(serde_json::from_str::<Config>(r#"{ "sensor": { "color_controls": { "gain": "high" } } }"#).is_err());
+        let config: Config = serde_json::from_str(r#"{ "outputs": [{ "sink": "osc" }, { "sink": "osc" }] }"#).unwrap();
+        assert!(config.validate().is_err());
+    }
+}
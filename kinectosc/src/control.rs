@@ -0,0 +1,128 @@
+use std::io;
+use std::net::UdpSocket;
+use std::sync::mpsc;
+use std::thread;
+
+use super::osc::{self, Argument, OscMessage};
+
+/// A runtime command received over OSC.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Moves the origin horizontally under the first selected body.
+    Recenter,
+    SetSmoothing(f64),
+    LockBody(u32),
+    /// Goes back to the configured selection policy.
+    UnlockBody,
+    Pause(bool),
+}
+
+type Method = fn(&[Argument]) -> Result<Command, String>;
+
+const METHODS: &[(&str, Method)] = &[
+    ("/kinectosc/recenter", |_| Ok(Command::Recenter)),
+    ("/kinectosc/filter/smoothing", |args| Ok(Command::SetSmoothing(number(args)?))),
+    ("/kinectosc/body/lock", |args| Ok(Command::LockBody(body_id(args)?))),
+    ("/kinectosc/body/unlock", |_| Ok(Command::UnlockBody)),
+    ("/kinectosc/pause", |args| Ok(Command::Pause(flag(args)?))),
+    ("/kinectosc/resume", |_| Ok(Command::Pause(false))),
+];
+
+/// Receives control messages on `socket` on a thread of its own, and hands over the
+/// commands they invoke. The thread stops once the receiver is dropped and another
+/// message arrives.
+pub fn listen(socket: UdpSocket) -> io::Result<mpsc::Receiver<Command>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("osc control".into())
+        .spawn(move || {
+            let mut buf = vec![0; 65536];
+            loop {
+                let (len, from) = match socket.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    // Windows reports ICMP port unreachable replies to what we sent here
+                    Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                    Err(e) => {
+                        eprintln!("Could not receive OSC control message: {}", e);
+                        continue;
+                    }
+                };
+                let packet = match osc::decode(&buf[..len]) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        eprintln!("Ignoring packet from {}: {}", from, e);
+                        continue;
+                    }
+                };
+                for message in packet.into_messages() {
+                    for command in commands(&message) {
+                        match command {
+                            Ok(command) => {
+                                if sender.send(command).is_err() {
+                                    return;
+                                }
+                            }
+                            Err(e) => eprintln!("Ignoring {} from {}: {}", message.address, from, e),
+                        }
+                    }
+                }
+            }
+        })?;
+    Ok(receiver)
+}
+
+/// Invokes every method the address pattern of `message` matches.
+fn commands(message: &OscMessage) -> Vec<Result<Command, String>> {
+    METHODS
+        .iter()
+        .filter(|(address, _)| osc::pattern_matches(&message.address, address))
+        .map(|(_, method)| method(&message.arguments))
+        .collect()
+}
+
+fn number(args: &[Argument]) -> Result<f64, String> {
+    match args {
+        [Argument::Float(f)] => Ok(*f as f64),
+        [Argument::Double(d)] => Ok(*d),
+        [Argument::Int(i)] => Ok(*i as f64),
+        _ => Err(format!("expected a number, got {:?}", args)),
+    }
+}
+
+fn body_id(args: &[Argument]) -> Result<u32, String> {
+    match args {
+        [Argument::Int(i)] if *i >= 0 => Ok(*i as u32),
+        [Argument::Long(h)] if *h >= 0 && *h <= u32::MAX as i64 => Ok(*h as u32),
+        _ => Err(format!("expected a body id, got {:?}", args)),
+    }
+}
+
+/// No argument counts as true.
+fn flag(args: &[Argument]) -> Result<bool, String> {
+    match args {
+        [] | [Argument::Impulse] => Ok(true),
+        [Argument::Bool(b)] => Ok(*b),
+        [Argument::Int(i)] => Ok(*i != 0),
+        _ => Err(format!("expected true or false, got {:?}", args)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        let message = |address: &str, arguments| OscMessage { address: address.into(), arguments };
+        assert_eq!(
+            vec![Ok(Command::SetSmoothing(0.5))],
+            commands(&message("/kinectosc/filter/smoothing", vec![Argument::Float(0.5)]))
+        );
+        assert_eq!(
+            vec![Ok(Command::Pause(true)), Ok(Command::Pause(false))],
+            commands(&message("/kinectosc/{pause,resume}", vec![]))
+        );
+        assert!(commands(&message("/kinectosc/body/lock", vec![Argument::Int(-1)]))[0].is_err());
+        assert!(commands(&message("/other", vec![])).is_empty());
+    }
+}
@@ -1,8 +1,10 @@
 use nalgebra::{Vector3, Point3, UnitQuaternion, Quaternion, Rotation3};
+use serde::Deserialize;
 
 const JOINT_COUNT: usize = k4a::joint_id::K4ABT_JOINT_COUNT as usize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SmoothParams {
     smoothing: f64,
     correction: f64,
@@ -24,6 +26,37 @@ impl Default for SmoothParams {
     }
 }
 
+impl SmoothParams {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in &[
+            ("smoothing", self.smoothing),
+            ("correction", self.correction),
+            ("prediction", self.prediction),
+        ] {
+            if !(0.0..=1.0).contains(value) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        for (name, value) in &[
+            ("jitter_radius", self.jitter_radius),
+            ("max_deviation_radius", self.max_deviation_radius),
+        ] {
+            if *value <= 0.0 {
+                return Err(format!("{} must be positive, got {}", name, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: f64) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&smoothing) {
+            return Err(format!("smoothing must be between 0 and 1, got {}", smoothing));
+        }
+        self.smoothing = smoothing;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FilteredJoint {
     pub raw_position: Point3<f64>,
@@ -145,6 +178,10 @@ impl KinectJointFilter {
         }
     }
 
+    pub fn set_params(&mut self, params: SmoothParams) {
+        self.params = params;
+    }
+
     pub fn update(&mut self, skeleton: &k4a::Skeleton) {
         for (idx, joint) in skeleton.joints.iter().enumerate() {
             self.joints[idx].update(self.params.clone(), joint);
@@ -0,0 +1,454 @@
+#[cfg(feature = "sensor")]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nalgebra::{Isometry3, Point3, Quaternion, UnitQuaternion, Vector3};
+
+use super::active_sensor;
+#[cfg(feature = "sensor")]
+use super::config::ColorControls;
+use super::config::FusionConfig;
+#[cfg(feature = "sensor")]
+use super::kinect::{self, Kinect};
+use super::skeleton_source::{BodyFrame, SkeletonSource};
+
+// how long a device thread waits for a result before checking whether to stop
+#[cfg(feature = "sensor")]
+const WORKER_TIMEOUT_MS: i32 = 100;
+
+/// One of the devices to track with.
+#[cfg(feature = "sensor")]
+pub struct DeviceSetup {
+    pub serial: String,
+    pub configuration: k4a::DeviceConfiguration,
+    /// Pose of the device in the shared space.
+    pub extrinsic: Isometry3<f64>,
+    pub color_controls: ColorControls,
+    pub record: Option<PathBuf>,
+}
+
+type TrackingResult = (usize, Result<BodyFrame, active_sensor::Error>);
+
+/// Tracks bodies with several devices, each on its own thread with its own tracker, and
+/// fuses what they see into one set of bodies in the shared space.
+pub struct MultiKinect {
+    is_running: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+    results: mpsc::Receiver<TrackingResult>,
+    // the latest result of each device since the last fused frame
+    pending: Vec<Option<BodyFrame>>,
+    window: Duration,
+    window_end: Option<Instant>,
+    fuser: Fuser,
+    // the device whose clock fused frames are timestamped with
+    reference: usize,
+    // device timestamp and capture time of the last frame of the reference device
+    reference_clock: (u64, Instant),
+}
+
+#[cfg(feature = "sensor")]
+impl MultiKinect {
+    pub fn open(
+        devices: &[DeviceSetup],
+        tracker_config: k4a::TrackerConfiguration,
+        options: &kinect::Options,
+        fusion: &FusionConfig,
+    ) -> Result<Self, k4a::OpenError> {
+        let mut opened = devices
+            .iter()
+            .map(|device| k4a::Device::open_by_serial(&device.serial).map(Some))
+            .collect::<Result<Vec<_>, _>>()?;
+        // subordinates wait for the master's sync pulses, so they have to be started first
+        let mut start_order: Vec<usize> = (0..devices.len()).collect();
+        start_order.sort_by_key(|index| {
+            devices[*index].configuration.wired_sync_mode == k4a::WiredSyncMode::K4A_WIRED_SYNC_MODE_MASTER
+        });
+        let mut kinects: Vec<Option<Kinect>> = devices.iter().map(|_| None).collect();
+        for index in start_order {
+            let device = opened[index].take().unwrap();
+            kinect::set_color_controls(&device, &devices[index].color_controls);
+            kinects[index] = Some(Kinect::open_device(
+                device,
+                devices[index].configuration.clone(),
+                tracker_config.clone(),
+                options,
+                devices[index].record.as_deref(),
+            )?);
+        }
+
+        // the master if there is one, since the others follow its clock
+        let reference = devices
+            .iter()
+            .position(|device| device.configuration.wired_sync_mode == k4a::WiredSyncMode::K4A_WIRED_SYNC_MODE_MASTER)
+            .unwrap_or(0);
+        let (sender, results) = mpsc::channel();
+        let mut multi_kinect = MultiKinect::new(results, devices.len(), reference, fusion);
+        multi_kinect.threads = kinects
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, kinect)| {
+                let extrinsic = devices[index].extrinsic;
+                let sender = sender.clone();
+                let is_running = multi_kinect.is_running.clone();
+                thread::spawn(move || track(index, kinect, extrinsic, sender, is_running))
+            })
+            .collect();
+        Ok(multi_kinect)
+    }
+}
+
+impl MultiKinect {
+    /// Fuses the results of `device_count` devices received from `results`, timestamped
+    /// with the clock of the device at index `reference`.
+    fn new(results: mpsc::Receiver<TrackingResult>, device_count: usize, reference: usize, fusion: &FusionConfig) -> Self {
+        MultiKinect {
+            is_running: Arc::new(AtomicBool::new(true)),
+            threads: Vec::new(),
+            results,
+            pending: (0..device_count).map(|_| None).collect(),
+            window: Duration::from_millis(fusion.window_ms),
+            window_end: None,
+            fuser: Fuser::new(fusion.match_distance_mm),
+            reference,
+            // device timestamps start about when the devices do
+            reference_clock: (0, Instant::now()),
+        }
+    }
+
+    /// The timestamp of `frames` on the clock of the reference device. Each device counts
+    /// from its own start, so when the reference device has no frame in the window, the
+    /// timestamp is carried on from its last one by the time between the captures.
+    fn reference_timestamp_usec(&mut self, frames: &[(usize, BodyFrame)], captured_at: Instant) -> u64 {
+        match frames.iter().find(|(index, _)| *index == self.reference) {
+            Some((_, frame)) => {
+                self.reference_clock = (frame.device_timestamp_usec, frame.captured_at);
+                frame.device_timestamp_usec
+            }
+            None => {
+                let (timestamp, at) = self.reference_clock;
+                timestamp + captured_at.saturating_duration_since(at).as_micros() as u64
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sensor")]
+fn track(
+    index: usize,
+    mut kinect: Kinect,
+    extrinsic: Isometry3<f64>,
+    sender: mpsc::Sender<TrackingResult>,
+    is_running: Arc<AtomicBool>,
+) {
+    while is_running.load(Ordering::SeqCst) {
+        let result = match kinect.next_frame(WORKER_TIMEOUT_MS) {
+            Ok(mut frame) => {
+                for body in frame.bodies.iter_mut() {
+                    transform_body(body, &extrinsic);
+                }
+                Ok(frame)
+            }
+            Err(active_sensor::Error::Timeout) => continue,
+            Err(e) => Err(e),
+        };
+        let failed = result.is_err();
+        if sender.send((index, result)).is_err() || failed {
+            break;
+        }
+    }
+}
+
+impl SkeletonSource for MultiKinect {
+    /// Waits for a result from every device, but no longer than `FusionConfig::window_ms`
+    /// after the first one, so that a lagging device does not hold back the others.
+    fn next_frame(&mut self, timeout: i32) -> Result<BodyFrame, active_sensor::Error> {
+        let deadline = (timeout >= 0).then(|| Instant::now() + Duration::from_millis(timeout as u64));
+        while self.pending.iter().any(Option::is_none) {
+            let wait_until = match (deadline, self.window_end) {
+                (Some(deadline), Some(window_end)) => Some(deadline.min(window_end)),
+                (deadline, window_end) => deadline.or(window_end),
+            };
+            let received = match wait_until {
+                Some(until) => self.results.recv_timeout(until.saturating_duration_since(Instant::now())),
+                None => self.results.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok((index, result)) => {
+                    self.pending[index] = Some(result?);
+                    let window = self.window;
+                    self.window_end.get_or_insert_with(|| Instant::now() + window);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) if self.window_end.is_some_and(|end| end <= Instant::now()) => {
+                    break;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => return Err(active_sensor::Error::Timeout),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(active_sensor::Error::Fatal("every device has stopped tracking".into()));
+                }
+            }
+        }
+        self.window_end = None;
+        let frames: Vec<(usize, BodyFrame)> = self
+            .pending
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, frame)| Some((index, frame.take()?)))
+            .collect();
+        let captured_at = frames.iter().map(|(_, frame)| frame.captured_at).min().unwrap();
+        let device_timestamp_usec = self.reference_timestamp_usec(&frames, captured_at);
+        let bodies = self.fuser.fuse(frames.into_iter().map(|(_, frame)| frame.bodies).collect());
+        Ok(BodyFrame {
+            device_timestamp_usec,
+            captured_at,
+            bodies,
+        })
+    }
+}
+
+impl Drop for MultiKinect {
+    fn drop(&mut self) {
+        self.is_running.store(false, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                eprintln!("A device thread panicked");
+            }
+        }
+    }
+}
+
+/// Merges the bodies seen by several devices into one body per person, and keeps the ids
+/// of fused bodies stable from frame to frame.
+pub struct Fuser {
+    match_distance_mm: f64,
+    // id and pelvis position of every body in the last fused frame
+    tracks: Vec<(u32, Point3<f64>)>,
+    next_id: u32,
+}
+
+impl Fuser {
+    pub fn new(match_distance_mm: f64) -> Self {
+        Self {
+            match_distance_mm,
+            tracks: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// `views` holds the bodies seen by each device, in the shared space.
+    pub fn fuse(&mut self, views: Vec<Vec<k4a::Body>>) -> Vec<k4a::Body> {
+        let mut clusters: Vec<Vec<k4a::Body>> = Vec::new();
+        for view in views {
+            // a device sees each person at most once
+            let mut taken = vec![false; clusters.len()];
+            for body in view {
+                let position = pelvis(&body.skeleton);
+                let nearest = nearest(
+                    (0..taken.len())
+                        .filter(|cluster| !taken[*cluster])
+                        .map(|cluster| (cluster, pelvis(&clusters[cluster][0].skeleton))),
+                    &position,
+                    self.match_distance_mm,
+                );
+                match nearest {
+                    Some(cluster) => {
+                        taken[cluster] = true;
+                        clusters[cluster].push(body);
+                    }
+                    None => clusters.push(vec![body]),
+                }
+            }
+        }
+
+        let mut previous = std::mem::take(&mut self.tracks);
+        let mut bodies = Vec::with_capacity(clusters.len());
+        for cluster in &clusters {
+            let skeleton = fuse_skeleton(cluster);
+            let position = pelvis(&skeleton);
+            let track = nearest(
+                previous.iter().enumerate().map(|(track, (_, p))| (track, *p)),
+                &position,
+                self.match_distance_mm,
+            );
+            let id = match track {
+                Some(track) => previous.swap_remove(track).0,
+                None => {
+                    self.next_id += 1;
+                    self.next_id - 1
+                }
+            };
+            self.tracks.push((id, position));
+            bodies.push(k4a::Body { id, skeleton });
+        }
+        bodies
+    }
+}
+
+/// The key of the candidate closest to `position`, if it is closer than `max_distance`.
+fn nearest<K, I>(candidates: I, position: &Point3<f64>, max_distance: f64) -> Option<K>
+where
+    I: Iterator<Item = (K, Point3<f64>)>,
+{
+    candidates
+        .map(|(key, candidate)| (key, nalgebra::distance(&candidate, position)))
+        .filter(|(_, distance)| *distance < max_distance)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(key, _)| key)
+}
+
+fn pelvis(skeleton: &k4a::Skeleton) -> Point3<f64> {
+    let p = &skeleton.joints[k4a::joint_id::K4ABT_JOINT_PELVIS as usize].position;
+    Point3::new(p.x as f64, p.y as f64, p.z as f64)
+}
+
+fn fuse_skeleton(bodies: &[k4a::Body]) -> k4a::Skeleton {
+    k4a::Skeleton {
+        joints: std::array::from_fn(|index| {
+            let joints: Vec<&k4a::Joint> = bodies.iter().map(|body| &body.skeleton.joints[index]).collect();
+            fuse_joint(&joints)
+        }),
+    }
+}
+
+/// Averages positions and orientations weighted by confidence, or evenly if no joint
+/// has any confidence.
+fn fuse_joint(joints: &[&k4a::Joint]) -> k4a::Joint {
+    let total_confidence: u32 = joints.iter().map(|joint| joint.confidence_level.0).sum();
+    let weight = |joint: &k4a::Joint| {
+        if total_confidence > 0 {
+            joint.confidence_level.0 as f64 / total_confidence as f64
+        } else {
+            1.0 / joints.len() as f64
+        }
+    };
+    let reference = quaternion(&joints[0].orientation);
+    let mut position = Vector3::zeros();
+    let mut orientation = Quaternion::new(0.0, 0.0, 0.0, 0.0);
+    for joint in joints {
+        let p = &joint.position;
+        position += Vector3::new(p.x as f64, p.y as f64, p.z as f64) * weight(joint);
+        // q and -q are the same rotation, average them on the same side
+        let q = quaternion(&joint.orientation);
+        let q = if q.dot(&reference) < 0.0 { -q } else { q };
+        orientation += q * weight(joint);
+    }
+    let orientation = UnitQuaternion::new_normalize(orientation);
+    k4a::Joint {
+        position: k4a::Float3 { x: position.x as f32, y: position.y as f32, z: position.z as f32 },
+        orientation: k4a::Quaternion {
+            w: orientation.w as f32,
+            x: orientation.i as f32,
+            y: orientation.j as f32,
+            z: orientation.k as f32,
+        },
+        confidence_level: joints.iter().map(|joint| joint.confidence_level).max_by_key(|level| level.0).unwrap(),
+    }
+}
+
+fn quaternion(q: &k4a::Quaternion) -> Quaternion<f64> {
+    Quaternion::new(q.w as f64, q.x as f64, q.y as f64, q.z as f64)
+}
+
+fn transform_body(body: &mut k4a::Body, extrinsic: &Isometry3<f64>) {
+    for joint in body.skeleton.joints.iter_mut() {
+        let p = &joint.position;
+        let p = extrinsic * Point3::new(p.x as f64, p.y as f64, p.z as f64);
+        joint.position = k4a::Float3 { x: p.x as f32, y: p.y as f32, z: p.z as f32 };
+        let q = extrinsic.rotation * UnitQuaternion::from_quaternion(quaternion(&joint.orientation));
+        joint.orientation = k4a::Quaternion { w: q.w as f32, x: q.i as f32, y: q.j as f32, z: q.k as f32 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeleton_source::{joint_at, test_body};
+
+    fn body(x: f32, confidence_level: k4a::JointConfidenceLevel) -> k4a::Body {
+        test_body(1, |_| k4a::Joint {
+            confidence_level,
+            ..joint_at(x, 0., 2000.)
+        })
+    }
+
+    #[test]
+    fn test_fuse() {
+        let high = k4a::JointConfidenceLevel::K4ABT_JOINT_CONFIDENCE_HIGH;
+        let low = k4a::JointConfidenceLevel::K4ABT_JOINT_CONFIDENCE_LOW;
+        let mut fuser = Fuser::new(300.);
+        let bodies = fuser.fuse(vec![vec![body(0., high)], vec![body(100., low), body(2000., low)]]);
+        assert_eq!(vec![1, 2], bodies.iter().map(|body| body.id).collect::<Vec<_>>());
+        assert!((pelvis(&bodies[0].skeleton).x - 25.).abs() < 1e-3);
+        assert_eq!(high, bodies[0].skeleton.joints[0].confidence_level);
+
+        // ids follow the bodies, not the order they are seen in
+        let bodies = fuser.fuse(vec![vec![body(1950., low), body(50., high)]]);
+        assert_eq!(vec![2, 1], bodies.iter().map(|body| body.id).collect::<Vec<_>>());
+    }
+
+    fn frame(device_timestamp_usec: u64, captured_at: Instant) -> BodyFrame {
+        BodyFrame {
+            device_timestamp_usec,
+            captured_at,
+            bodies: Vec::new(),
+        }
+    }
+
+    fn multi_kinect(device_count: usize, reference: usize) -> (MultiKinect, mpsc::Sender<TrackingResult>) {
+        let (sender, results) = mpsc::channel();
+        let fusion = FusionConfig {
+            window_ms: 50,
+            ..FusionConfig::default()
+        };
+        (MultiKinect::new(results, device_count, reference, &fusion), sender)
+    }
+
+    #[test]
+    fn test_reference_timestamp() {
+        let (mut multi_kinect, sender) = multi_kinect(2, 1);
+        let start = Instant::now();
+        sender.send((0, Ok(frame(900_000, start)))).unwrap();
+        sender.send((1, Ok(frame(1_000_000, start + Duration::from_millis(2))))).unwrap();
+        let fused = multi_kinect.next_frame(1000).unwrap();
+        assert_eq!(1_000_000, fused.device_timestamp_usec);
+        assert_eq!(start, fused.captured_at);
+
+        // without the reference device, its clock is carried on
+        sender.send((0, Ok(frame(933_333, start + Duration::from_millis(35))))).unwrap();
+        let fused = multi_kinect.next_frame(1000).unwrap();
+        assert_eq!(1_033_000, fused.device_timestamp_usec);
+    }
+
+    #[test]
+    fn test_window() {
+        let (mut multi_kinect, sender) = multi_kinect(2, 0);
+        let start = Instant::now();
+        sender.send((0, Ok(frame(0, start)))).unwrap();
+        // the lagging device is waited for only until the window closes
+        let fused = multi_kinect.next_frame(1000).unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_millis(1000));
+        assert_eq!(0, fused.device_timestamp_usec);
+
+        // and its late result goes to the next frame
+        sender.send((1, Ok(frame(0, Instant::now())))).unwrap();
+        sender.send((0, Ok(frame(33_333, Instant::now())))).unwrap();
+        assert_eq!(33_333, multi_kinect.next_frame(1000).unwrap().device_timestamp_usec);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (mut multi_kinect, sender) = multi_kinect(2, 0);
+        // the window does not open until a device has a result
+        assert!(matches!(multi_kinect.next_frame(20), Err(active_sensor::Error::Timeout)));
+
+        sender.send((1, Err(active_sensor::Error::Disconnected))).unwrap();
+        assert!(matches!(multi_kinect.next_frame(20), Err(active_sensor::Error::Disconnected)));
+
+        drop(sender);
+        assert!(matches!(multi_kinect.next_frame(20), Err(active_sensor::Error::Fatal(_))));
+    }
+}
@@ -0,0 +1,107 @@
+use nalgebra::{Matrix3, Rotation3, UnitQuaternion, Vector3};
+
+const STANDARD_GRAVITY: f64 = 9.80665;
+// samples further than this from 1 g are taken while the sensor moves and are ignored
+const MAX_DEVIATION: f64 = 0.5;
+// weight of each new sample in the running average
+const SMOOTHING: f64 = 0.02;
+// samples needed before the estimate is trusted (the IMU runs at about 1.6 kHz)
+const MIN_SAMPLES: u32 = 200;
+
+/// Estimates the direction of gravity from accelerometer samples, and the rotation
+/// that levels the depth camera coordinate system so that its -y axis points up.
+pub struct GravityEstimator {
+    accel_to_depth: Rotation3<f64>,
+    up: Vector3<f64>,
+    samples: u32,
+}
+
+impl GravityEstimator {
+    pub fn new(accel_to_depth: Rotation3<f64>) -> Self {
+        Self {
+            accel_to_depth,
+            up: Vector3::zeros(),
+            samples: 0,
+        }
+    }
+
+    pub fn from_calibration(calibration: &k4a::Calibration) -> Self {
+        let accel = k4a::CalibrationType::K4A_CALIBRATION_TYPE_ACCEL.0 as usize;
+        let depth = k4a::CalibrationType::K4A_CALIBRATION_TYPE_DEPTH.0 as usize;
+        let rotation = Matrix3::from_iterator(
+            calibration.extrinsics[accel][depth].rotation.iter().map(|&v| v as f64),
+        );
+        // the SDK stores the matrix row major, nalgebra fills it column major
+        Self::new(Rotation3::from_matrix_unchecked(rotation.transpose()))
+    }
+
+    pub fn update(&mut self, imu_sample: &k4a::ImuSample) {
+        let acc = &imu_sample.acc_sample;
+        // at rest the accelerometer measures the reaction to gravity, which points up
+        let up = self.accel_to_depth * Vector3::new(acc.x as f64, acc.y as f64, acc.z as f64);
+        if (up.norm() - STANDARD_GRAVITY).abs() > MAX_DEVIATION {
+            return;
+        }
+        if self.samples == 0 {
+            self.up = up;
+        } else {
+            self.up = self.up.lerp(&up, SMOOTHING);
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// Returns `None` until enough samples were taken at rest.
+    pub fn leveling(&self) -> Option<UnitQuaternion<f64>> {
+        if self.samples < MIN_SAMPLES {
+            return None;
+        }
+        UnitQuaternion::rotation_between(&self.up, &-Vector3::y())
+    }
+}
+
+/// Rotates every joint of `body` about the depth camera origin.
+pub fn level_body(body: &mut k4a::Body, leveling: &UnitQuaternion<f64>) {
+    for joint in body.skeleton.joints.iter_mut() {
+        let p = &joint.position;
+        let p = leveling * Vector3::new(p.x as f64, p.y as f64, p.z as f64);
+        joint.position = k4a::Float3 { x: p.x as f32, y: p.y as f32, z: p.z as f32 };
+        let q = &joint.orientation;
+        let q = leveling * UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(
+            q.w as f64, q.x as f64, q.y as f64, q.z as f64,
+        ));
+        joint.orientation = k4a::Quaternion { w: q.w as f32, x: q.i as f32, y: q.j as f32, z: q.k as f32 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(x: f32, y: f32, z: f32) -> k4a::ImuSample {
+        k4a::ImuSample {
+            temperature: 25.0,
+            acc_sample: k4a::Float3 { x, y, z },
+            acc_timestamp_usec: 0,
+            gyro_sample: k4a::Float3 { x: 0.0, y: 0.0, z: 0.0 },
+            gyro_timestamp_usec: 0,
+        }
+    }
+
+    #[test]
+    fn test_leveling_tilted_sensor() {
+        let mut estimator = GravityEstimator::new(Rotation3::identity());
+        // pitched down by 30 degrees
+        let (sin, cos) = (30f32.to_radians().sin(), 30f32.to_radians().cos());
+        let g = STANDARD_GRAVITY as f32;
+        for _ in 0..MIN_SAMPLES {
+            assert!(estimator.leveling().is_none());
+            estimator.update(&sample(0.0, -g * cos, g * sin));
+            // a shake, ignored
+            estimator.update(&sample(5.0, -20.0, 0.0));
+        }
+        let leveling = estimator.leveling().unwrap();
+        let up = leveling * Vector3::new(0.0, -cos as f64, sin as f64);
+        assert!((up - -Vector3::y()).norm() < 1e-6);
+        assert!((leveling.angle() - 30f64.to_radians()).abs() < 1e-6);
+    }
+}
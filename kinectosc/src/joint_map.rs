@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+use std::{fs, io, path};
+
+use k4a::joint_id;
+use nalgebra::{Point3, Quaternion, Translation3, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::calibration::{Rotation, Translation};
+use super::filter::FilteredJoint;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JointName {
+    Pelvis,
+    SpineNavel,
+    SpineChest,
+    Neck,
+    ClavicleLeft,
+    ShoulderLeft,
+    ElbowLeft,
+    WristLeft,
+    HandLeft,
+    HandtipLeft,
+    ThumbLeft,
+    ClavicleRight,
+    ShoulderRight,
+    ElbowRight,
+    WristRight,
+    HandRight,
+    HandtipRight,
+    ThumbRight,
+    HipLeft,
+    KneeLeft,
+    AnkleLeft,
+    FootLeft,
+    HipRight,
+    KneeRight,
+    AnkleRight,
+    FootRight,
+    Head,
+    Nose,
+    EyeLeft,
+    EarLeft,
+    EyeRight,
+    EarRight,
+}
+
+impl JointName {
+    pub fn id(self) -> joint_id::Type {
+        match self {
+            JointName::Pelvis => joint_id::K4ABT_JOINT_PELVIS,
+            JointName::SpineNavel => joint_id::K4ABT_JOINT_SPINE_NAVEL,
+            JointName::SpineChest => joint_id::K4ABT_JOINT_SPINE_CHEST,
+            JointName::Neck => joint_id::K4ABT_JOINT_NECK,
+            JointName::ClavicleLeft => joint_id::K4ABT_JOINT_CLAVICLE_LEFT,
+            JointName::ShoulderLeft => joint_id::K4ABT_JOINT_SHOULDER_LEFT,
+            JointName::ElbowLeft => joint_id::K4ABT_JOINT_ELBOW_LEFT,
+            JointName::WristLeft => joint_id::K4ABT_JOINT_WRIST_LEFT,
+            JointName::HandLeft => joint_id::K4ABT_JOINT_HAND_LEFT,
+            JointName::HandtipLeft => joint_id::K4ABT_JOINT_HANDTIP_LEFT,
+            JointName::ThumbLeft => joint_id::K4ABT_JOINT_THUMB_LEFT,
+            JointName::ClavicleRight => joint_id::K4ABT_JOINT_CLAVICLE_RIGHT,
+            JointName::ShoulderRight => joint_id::K4ABT_JOINT_SHOULDER_RIGHT,
+            JointName::ElbowRight => joint_id::K4ABT_JOINT_ELBOW_RIGHT,
+            JointName::WristRight => joint_id::K4ABT_JOINT_WRIST_RIGHT,
+            JointName::HandRight => joint_id::K4ABT_JOINT_HAND_RIGHT,
+            JointName::HandtipRight => joint_id::K4ABT_JOINT_HANDTIP_RIGHT,
+            JointName::ThumbRight => joint_id::K4ABT_JOINT_THUMB_RIGHT,
+            JointName::HipLeft => joint_id::K4ABT_JOINT_HIP_LEFT,
+            JointName::KneeLeft => joint_id::K4ABT_JOINT_KNEE_LEFT,
+            JointName::AnkleLeft => joint_id::K4ABT_JOINT_ANKLE_LEFT,
+            JointName::FootLeft => joint_id::K4ABT_JOINT_FOOT_LEFT,
+            JointName::HipRight => joint_id::K4ABT_JOINT_HIP_RIGHT,
+            JointName::KneeRight => joint_id::K4ABT_JOINT_KNEE_RIGHT,
+            JointName::AnkleRight => joint_id::K4ABT_JOINT_ANKLE_RIGHT,
+            JointName::FootRight => joint_id::K4ABT_JOINT_FOOT_RIGHT,
+            JointName::Head => joint_id::K4ABT_JOINT_HEAD,
+            JointName::Nose => joint_id::K4ABT_JOINT_NOSE,
+            JointName::EyeLeft => joint_id::K4ABT_JOINT_EYE_LEFT,
+            JointName::EarLeft => joint_id::K4ABT_JOINT_EAR_LEFT,
+            JointName::EyeRight => joint_id::K4ABT_JOINT_EYE_RIGHT,
+            JointName::EarRight => joint_id::K4ABT_JOINT_EAR_RIGHT,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.id() as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Output {
+    Raw,
+    Filtered,
+    Predicted,
+}
+
+/// Fixed transform in the joint's local frame. The translation is in millimeters,
+/// like the joint positions reported by the tracker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Offset {
+    translation: Translation,
+    rotation: Rotation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackerMapping {
+    pub joint: JointName,
+    pub id: u32,
+    #[serde(default)]
+    pub offset: Option<Offset>,
+    #[serde(default = "default_position_output")]
+    pub position: Output,
+    #[serde(default = "default_orientation_output")]
+    pub orientation: Output,
+}
+
+fn default_position_output() -> Output {
+    Output::Predicted
+}
+
+fn default_orientation_output() -> Output {
+    Output::Raw
+}
+
+impl TrackerMapping {
+    pub fn new(joint: JointName, id: u32) -> Self {
+        Self {
+            joint,
+            id,
+            offset: None,
+            position: default_position_output(),
+            orientation: default_orientation_output(),
+        }
+    }
+
+    pub fn pose(&self, joint: &FilteredJoint) -> TrackerPose {
+        let position = match self.position {
+            Output::Raw => joint.raw_position,
+            Output::Filtered => joint.filtered_position,
+            Output::Predicted => joint.predicted_position,
+        };
+        let orientation = match self.orientation {
+            Output::Raw => joint.raw_orientation,
+            Output::Filtered => joint.filtered_orientation,
+            Output::Predicted => joint.predicted_orientation,
+        };
+        let (position, orientation) = match &self.offset {
+            Some(offset) => {
+                let translation: Translation3<f64> = offset.translation.clone().into();
+                let rotation: Quaternion<f64> = offset.rotation.clone().into();
+                (
+                    position + orientation * translation.vector,
+                    orientation * UnitQuaternion::from_quaternion(rotation),
+                )
+            }
+            None => (position, orientation),
+        };
+        TrackerPose {
+            position,
+            orientation,
+            velocity: joint.trend,
+            is_valid: joint.frame_count > 1,
+        }
+    }
+}
+
+pub struct TrackerPose {
+    pub position: Point3<f64>,
+    pub orientation: UnitQuaternion<f64>,
+    pub velocity: Vector3<f64>,
+    pub is_valid: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JointMap {
+    pub trackers: Vec<TrackerMapping>,
+}
+
+impl JointMap {
+    /// Distance between the tracker ids of consecutive bodies.
+    pub fn id_stride(&self) -> u32 {
+        self.trackers.iter().map(|tracker| tracker.id + 1).max().unwrap_or(0)
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let mut ids = HashSet::new();
+        for tracker in &self.trackers {
+            if !ids.insert(tracker.id) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("tracker id {} is mapped more than once", tracker.id),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for JointMap {
+    fn default() -> Self {
+        Self {
+            trackers: vec![
+                TrackerMapping::new(JointName::Pelvis, 0),
+                TrackerMapping::new(JointName::FootLeft, 1),
+                TrackerMapping::new(JointName::FootRight, 2),
+                TrackerMapping::new(JointName::HandLeft, 3),
+                TrackerMapping::new(JointName::HandRight, 4),
+            ],
+        }
+    }
+}
+
+pub fn load<P: AsRef<path::Path>>(path: P) -> io::Result<JointMap> {
+    let file = fs::File::open(path)?;
+    let joint_map: JointMap = serde_json::from_reader(file)?;
+    joint_map.validate()?;
+    Ok(joint_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn joint() -> FilteredJoint {
+        FilteredJoint {
+            raw_position: Point3::new(1., 2., 3.),
+            filtered_position: Point3::new(4., 5., 6.),
+            predicted_position: Point3::new(7., 8., 9.),
+            raw_orientation: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2),
+            filtered_orientation: UnitQuaternion::from_axis_angle(&Vector3::x_axis(), FRAC_PI_2),
+            trend: Vector3::new(0., 0., 10.),
+            frame_count: 2,
+            ..FilteredJoint::default()
+        }
+    }
+
+    #[test]
+    fn test_pose_outputs() {
+        let mut mapping = TrackerMapping::new(JointName::Pelvis, 0);
+        let pose = mapping.pose(&joint());
+        // predicted position and raw orientation by default
+        assert_eq!(Point3::new(7., 8., 9.), pose.position);
+        assert_eq!(joint().raw_orientation, pose.orientation);
+        assert_eq!(Vector3::new(0., 0., 10.), pose.velocity);
+        assert!(pose.is_valid);
+
+        mapping.position = Output::Filtered;
+        mapping.orientation = Output::Predicted;
+        let pose = mapping.pose(&joint());
+        assert_eq!(Point3::new(4., 5., 6.), pose.position);
+        assert_eq!(UnitQuaternion::identity(), pose.orientation);
+
+        mapping.position = Output::Raw;
+        mapping.orientation = Output::Filtered;
+        let pose = mapping.pose(&FilteredJoint { frame_count: 1, ..joint() });
+        assert_eq!(Point3::new(1., 2., 3.), pose.position);
+        assert_eq!(joint().filtered_orientation, pose.orientation);
+        // a single frame has no trend yet
+        assert!(!pose.is_valid);
+    }
+
+    #[test]
+    fn test_pose_offset() {
+        let mapping: TrackerMapping = serde_json::from_str(
+            r#"{
+                "joint": "foot_left", "id": 1, "position": "raw", "orientation": "filtered",
+                "offset": {
+                    "translation": { "x": 0, "y": 0, "z": 100 },
+                    "rotation": { "w": 0.7071067811865476, "i": 0, "j": 0.7071067811865476, "k": 0 }
+                }
+            }"#,
+        )
+        .unwrap();
+        let pose = mapping.pose(&joint());
+        // the offset is in the joint's frame, which is turned 90 degrees about x
+        assert!((pose.position - Point3::new(1., -98., 3.)).norm() < 1e-9, "{}", pose.position);
+        let expected = joint().filtered_orientation * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2);
+        assert!(pose.orientation.angle_to(&expected) < 1e-9);
+    }
+
+    #[test]
+    fn test_validate() {
+        JointMap::default().validate().unwrap();
+        let mut joint_map = JointMap::default();
+        joint_map.trackers.push(TrackerMapping::new(JointName::Head, 2));
+        assert_eq!(io::ErrorKind::InvalidData, joint_map.validate().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("kinectosc-joint-map-{}.json", std::process::id()));
+        let load_json = |json: &str| {
+            fs::write(&path, json).unwrap();
+            load(&path)
+        };
+        let joint_map = load_json(r#"{ "trackers": [{ "joint": "head", "id": 5 }, { "joint": "nose", "id": 2 }] }"#).unwrap();
+        assert_eq!(JointName::Head, joint_map.trackers[0].joint);
+        assert_eq!(Output::Predicted, joint_map.trackers[0].position);
+        assert_eq!(6, joint_map.id_stride());
+
+        let duplicate = load_json(r#"{ "trackers": [{ "joint": "head", "id": 5 }, { "joint": "nose", "id": 5 }] }"#);
+        assert_eq!(io::ErrorKind::InvalidData, duplicate.unwrap_err().kind());
+        let unknown_field = load_json(
+            r#"{ "trackers": [{ "joint": "head", "id": 5, "offset": {
+                "translation": { "x": 0, "y": 0, "z": 0 }, "rotation": { "w": 1, "i": 0, "j": 0, "k": 0 },
+                "scale": 2 } }] }"#,
+        );
+        assert_eq!(io::ErrorKind::InvalidData, unknown_field.unwrap_err().kind());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(io::ErrorKind::NotFound, load(&path).unwrap_err().kind());
+    }
+}
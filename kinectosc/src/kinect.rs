@@ -1,30 +1,219 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use nalgebra::UnitQuaternion;
+
 use super::active_sensor::{self, ActiveSensor};
+use super::config;
+use super::gravity::{self, GravityEstimator};
+use super::metrics::Metrics;
+use super::pipeline::Pipeline;
+use super::skeleton_source::{BodyFrame, SkeletonSource};
 
-pub struct Kinect {
+pub struct Options {
+    pub level_with_gravity: bool,
+    /// Run capture and tracking on separate threads, buffering up to this many captures.
+    pub pipeline_depth: Option<usize>,
+    pub temporal_smoothing: Option<f32>,
+    /// Where the pipeline reports its stages.
+    pub metrics: Arc<Mutex<Metrics>>,
+}
+
+/// Reports, but otherwise skips, the controls the device rejects.
+pub fn set_color_controls(device: &k4a::Device, color_controls: &config::ColorControls) {
+    for (control, setting) in color_controls {
+        let (mode, value) = setting.to_k4a();
+        if let Err(e) = device.set_color_control((*control).into(), mode, value) {
+            eprintln!("Could not set color control: {}", e);
+        }
+    }
+}
+
+/// Reads captures, and IMU samples to keep the gravity estimate up to date.
+pub struct SensorReader {
     sensor: Box<dyn ActiveSensor>,
-    tracker: k4a::Tracker,
+    gravity: Option<GravityEstimator>,
+    // the first sample after the last capture, read but not yet used
+    next_imu_sample: Option<k4a::ImuSample>,
+    last_timestamp_usec: Option<u64>,
+}
+
+impl SensorReader {
+    fn new(sensor: Box<dyn ActiveSensor>, gravity: Option<GravityEstimator>) -> Self {
+        SensorReader {
+            sensor,
+            gravity,
+            next_imu_sample: None,
+            last_timestamp_usec: None,
+        }
+    }
+
+    /// Reads a capture, and the IMU samples taken up to it. A playback has samples for
+    /// the whole recording ready, so reading until none is left would read them all.
+    pub fn read(&mut self, timeout: i32) -> Result<k4a::Capture, active_sensor::Error> {
+        let capture = self.sensor.get_capture(timeout)?;
+        let timestamp_usec = match capture.get_device_timestamp_usec() {
+            Some(timestamp_usec) => timestamp_usec,
+            None => return Ok(capture),
+        };
+        if self.last_timestamp_usec.is_some_and(|last| timestamp_usec < last) {
+            // the playback was rewound, and the samples with it
+            self.next_imu_sample = None;
+        }
+        self.last_timestamp_usec = Some(timestamp_usec);
+        if let Some(gravity) = &mut self.gravity {
+            loop {
+                let imu_sample = match self.next_imu_sample.take() {
+                    Some(imu_sample) => imu_sample,
+                    None => match self.sensor.get_imu_sample(0) {
+                        Ok(imu_sample) => imu_sample,
+                        Err(active_sensor::Error::Timeout) | Err(active_sensor::Error::End) => break,
+                        Err(e) => return Err(e),
+                    },
+                };
+                if imu_sample.acc_timestamp_usec > timestamp_usec {
+                    self.next_imu_sample = Some(imu_sample);
+                    break;
+                }
+                gravity.update(&imu_sample);
+            }
+        }
+        Ok(capture)
+    }
+
+    pub fn leveling(&self) -> Option<UnitQuaternion<f64>> {
+        self.gravity.as_ref().and_then(|gravity| gravity.leveling())
+    }
+}
+
+enum Input {
+    /// With when the capture of a result that timed out was read, while the result is
+    /// still in the tracker.
+    Serial(SensorReader, Option<Instant>),
+    Pipelined(Pipeline),
+}
+
+pub struct Kinect {
+    // dropped before the sensor, so that the tracker releases its captures first
+    tracker: Arc<k4a::Tracker>,
+    input: Input,
 }
 
 impl Kinect {
-    pub fn open_sensor(
-        device_index: u32,
+    pub fn open_device(
+        device: k4a::Device,
         sensor_config: k4a::DeviceConfiguration,
-        tracker_config: k4a::TrackerConfiguration
+        tracker_config: k4a::TrackerConfiguration,
+        options: &Options,
+        record: Option<&Path>,
+    ) -> Result<Self, k4a::Error> {
+        let mut running = device
+            .start_cameras(sensor_config.clone())?;
+        if options.level_with_gravity {
+            running.start_imu()?;
+        }
+        let recorder = match record {
+            Some(path) => {
+                let recorder = k4a::Recorder::create(&path.to_string_lossy(), Some(&running), &sensor_config)?;
+                if options.level_with_gravity {
+                    recorder.add_imu_track()?;
+                }
+                recorder.write_header()?;
+                Some(recorder)
+            }
+            None => None,
+        };
+        let sensor: Box<dyn ActiveSensor> = match recorder {
+            Some(recorder) => Box::new(active_sensor::RecordingSensor::new(Box::new(running), recorder)),
+            None => Box::new(running),
+        };
+        Self::open(sensor, tracker_config, options)
+    }
+
+    pub fn open_playback(
+        playback: active_sensor::RealtimePlayback,
+        tracker_config: k4a::TrackerConfiguration,
+        options: &Options,
+    ) -> Result<Self, k4a::Error> {
+        Self::open(Box::new(playback), tracker_config, options)
+    }
+
+    fn open(
+        sensor: Box<dyn ActiveSensor>,
+        tracker_config: k4a::TrackerConfiguration,
+        options: &Options,
     ) -> Result<Self, k4a::Error> {
-        let device = k4a::Device::open(device_index)?;
-        let running = device
-            .start_cameras(sensor_config)?;
-        let calibration = running.get_calibration()?;
-        let tracker = k4a::Tracker::create(&calibration, tracker_config)?;
-        Ok(Self {
-            sensor: Box::new(running),
-            tracker,
-        })
-    }
-
-    pub fn next_frame(&self) -> Result<k4a::Frame, active_sensor::Error> {
-        let capture = self.sensor.get_capture(-1)?;
-        self.tracker.enqueue_capture(capture, -1)?;
-        Ok(self.tracker.k4abt_tracker_pop_result(-1)?)
+        let calibration = sensor.get_calibration()?;
+        let tracker = Arc::new(k4a::Tracker::create(&calibration, tracker_config)?);
+        if let Some(smoothing_factor) = options.temporal_smoothing {
+            tracker.set_temporal_smoothing(smoothing_factor);
+        }
+        let gravity = if options.level_with_gravity {
+            Some(GravityEstimator::from_calibration(&calibration))
+        } else {
+            None
+        };
+        let reader = SensorReader::new(sensor, gravity);
+        let input = match options.pipeline_depth {
+            Some(depth) => Input::Pipelined(Pipeline::start(reader, tracker.clone(), depth, options.metrics.clone())),
+            None => Input::Serial(reader, None),
+        };
+        Ok(Self { tracker, input })
+    }
+
+    /// Returns the body tracking result, and when its capture was read.
+    pub fn pop_frame(&mut self, timeout: i32) -> Result<(k4a::Frame, Instant), active_sensor::Error> {
+        match &mut self.input {
+            Input::Serial(reader, pending) => {
+                // pop a result that timed out before enqueuing another capture, or results
+                // back up in the tracker and each comes out a frame later
+                let captured_at = match pending.take() {
+                    Some(captured_at) => captured_at,
+                    None => {
+                        let capture = reader.read(timeout)?;
+                        let captured_at = Instant::now();
+                        self.tracker.enqueue_capture(capture, timeout)?;
+                        captured_at
+                    }
+                };
+                match self.tracker.k4abt_tracker_pop_result(timeout) {
+                    Ok(frame) => Ok((frame, captured_at)),
+                    Err(k4a::WaitError::Timeout) => {
+                        *pending = Some(captured_at);
+                        Err(active_sensor::Error::Timeout)
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Input::Pipelined(pipeline) => match self.tracker.k4abt_tracker_pop_result(timeout) {
+                Ok(frame) => {
+                    let captured_at = pipeline.on_popped(frame.get_device_timestamp_usec());
+                    Ok((frame, captured_at.unwrap_or_else(Instant::now)))
+                }
+                Err(k4a::WaitError::Timeout) => Err(pipeline.take_error().unwrap_or(active_sensor::Error::Timeout)),
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
+
+    fn leveling(&self) -> Option<UnitQuaternion<f64>> {
+        match &self.input {
+            Input::Serial(reader, _) => reader.leveling(),
+            Input::Pipelined(pipeline) => pipeline.leveling(),
+        }
+    }
+}
+
+impl SkeletonSource for Kinect {
+    fn next_frame(&mut self, timeout: i32) -> Result<BodyFrame, active_sensor::Error> {
+        let (frame, captured_at) = self.pop_frame(timeout)?;
+        let mut body_frame = BodyFrame::from_frame(&frame, captured_at)?;
+        if let Some(leveling) = self.leveling() {
+            for body in body_frame.bodies.iter_mut() {
+                gravity::level_body(body, &leveling);
+            }
+        }
+        Ok(body_frame)
     }
 }
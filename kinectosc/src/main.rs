@@ -1,89 +1,440 @@
-use std::net::SocketAddr;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+// without the sensor only synthetic and replayed skeletons are tracked, so part of the
+// tree goes unused
+#![cfg_attr(not(feature = "sensor"), allow(dead_code))]
 
-use k4a::joint_id;
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 mod active_sensor;
+mod backoff;
+mod body_selector;
 mod osc;
+#[cfg(feature = "sensor")]
+mod pipeline;
 mod calibration;
+mod config;
+mod control;
+#[cfg(feature = "sensor")]
 mod kinect;
+mod metrics;
 mod profile_provider;
+#[cfg(feature = "sensor")]
+mod reconnect;
 mod filter;
+mod fusion;
+#[cfg(feature = "sensor")]
+mod gravity;
+mod joint_map;
+#[cfg(feature = "sensor")]
+mod sensor;
+mod skeleton_source;
+mod skeleton_file;
+mod synthetic;
+mod sink;
+mod transport;
+mod vmc;
+
+use nalgebra::{Isometry3, Vector3};
+use skeleton_source::SkeletonSource;
+use structopt::StructOpt;
+
+const DEFAULT_CONFIG_PATH: &str = "kinectosc.json";
+const FRAME_TIMEOUT_MS: i32 = 1000;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "kinectosc", about = "Sends Azure Kinect body tracking results as OSC trackers")]
+struct Opt {
+    /// Configuration file [default: kinectosc.json if it exists]
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Address to send OSC messages to, instead of `osc.destinations`
+    #[structopt(long)]
+    target: Option<SocketAddr>,
+    /// Address to bind the OSC socket to
+    #[structopt(long)]
+    bind: Option<SocketAddr>,
+    /// Index of the Azure Kinect device to open
+    #[structopt(long)]
+    device: Option<u32>,
+    /// Serial number of the Azure Kinect device to open, see `kinectosc devices`
+    #[structopt(long, conflicts_with = "device")]
+    serial: Option<String>,
+    /// Calibration profile path
+    #[structopt(long, parse(from_os_str))]
+    profile: Option<PathBuf>,
+    /// Joint mapping path
+    #[structopt(long, parse(from_os_str))]
+    joint_map: Option<PathBuf>,
+    /// Generate skeletons instead of opening a device
+    #[structopt(long)]
+    synthetic: bool,
+    /// Replay a skeleton file instead of opening a device
+    #[structopt(long, parse(from_os_str), conflicts_with = "synthetic")]
+    replay: Option<PathBuf>,
+    /// Run body tracking on an Azure Kinect recording instead of opening a device
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["synthetic", "replay"])]
+    playback: Option<PathBuf>,
+    /// Start the recording at this many seconds in
+    #[structopt(long, requires = "playback")]
+    start: Option<f64>,
+    /// Stop the recording at this many seconds in
+    #[structopt(long, requires = "playback")]
+    end: Option<f64>,
+    /// Play the recording over and over
+    #[structopt(long = "loop", requires = "playback")]
+    looping: bool,
+    /// Record every capture to an Azure Kinect recording (.mkv) while tracking. After a
+    /// reconnect, recording continues in a new file with a numbered name. With several
+    /// devices, each records to its own file named after its serial number
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["synthetic", "replay", "playback"])]
+    record: Option<PathBuf>,
+    /// Write every body tracking result to a skeleton file
+    #[structopt(long, parse(from_os_str))]
+    dump: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// List the connected Azure Kinect devices with their serial numbers and firmware versions
+    Devices,
+    /// Print the current color controls of the device as a `sensor.color_controls` section
+    ColorControls,
+}
+
+fn load_config(opt: &Opt) -> Result<config::Config, config::Error> {
+    let mut config = match &opt.config {
+        Some(path) => config::Config::load(path)?,
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => config::Config::load(DEFAULT_CONFIG_PATH)?,
+        None => config::Config::default(),
+    };
+    if let Some(target) = opt.target {
+        config.osc.target = target;
+        config.osc.destinations.clear();
+    }
+    if let Some(bind) = opt.bind {
+        config.osc.bind = bind;
+    }
+    if let Some(device) = opt.device {
+        config.sensor.device_index = device;
+        config.sensor.serial = None;
+    }
+    if let Some(serial) = &opt.serial {
+        config.sensor.serial = Some(serial.clone());
+    }
+    if let Some(profile) = &opt.profile {
+        config.profile = profile.clone();
+    }
+    if let Some(joint_map) = &opt.joint_map {
+        config.joint_map = joint_map.clone();
+    }
+    let start = opt.start.unwrap_or(0.0);
+    if start < 0.0 || opt.end.is_some_and(|end| end <= start) {
+        return Err(config::Error::Invalid("--end must come after --start, which must not be negative".into()));
+    }
+    config.validate()?;
+    Ok(config)
+}
 
 fn main() {
+    let opt = Opt::from_args();
+    if let Some(Command::Devices) = opt.command {
+        sensor::list_devices();
+        return;
+    }
+    let config = match load_config(&opt) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::ColorControls) = opt.command {
+        if let Err(e) = sensor::print_color_controls(&config.sensor) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    sensor::capture_sdk_log();
+
     let is_running = Arc::new(AtomicBool::new(true));
     let r = is_running.clone();
     ctrlc::set_handler(move || {
         r.store(false, Ordering::SeqCst);
     }).unwrap();
 
-    let mut profile = profile_provider::ProfileProvider::new("calibration_profile.json".into());
+    let joint_map = match joint_map::load(&config.joint_map) {
+        Ok(joint_map) => joint_map,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => joint_map::JointMap::default(),
+        Err(e) => {
+            eprintln!("error: could not load joint map {}: {}", config.joint_map.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    // without a profile the poses are sent uncalibrated, like the identity profile the
+    // calibrator starts from
+    let mut profile = match profile_provider::ProfileProvider::new(config.profile.clone()) {
+        Ok(profile) => Some(profile),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("No calibration profile at {}, sending uncalibrated poses", config.profile.display());
+            None
+        }
+        Err(e) => {
+            eprintln!("error: could not load calibration profile {}: {}", config.profile.display(), e);
+            std::process::exit(1);
+        }
+    };
 
-    let target_addr: SocketAddr = "127.0.0.1:8124"
-        .parse()
-        .expect("failed to parse target addr");
-    let mut osc_client = osc::Client::new("0.0.0.0:9010", target_addr).unwrap();
+    let mut control_socket = None;
+    let mut sinks: Vec<Box<dyn sink::OutputSink>> = Vec::new();
+    for output in &config.outputs {
+        let output_sink: Box<dyn sink::OutputSink> = match output {
+            config::Output::Osc => {
+                let client = match osc::Client::new(config.osc.bind, &config.osc.destinations()) {
+                    Ok(client) => client,
+                    Err(e) => {
+                        eprintln!("error: could not open OSC output bound to {}: {}", config.osc.bind, e);
+                        std::process::exit(1);
+                    }
+                };
+                control_socket = Some(client.try_clone_socket());
+                match config.osc.format {
+                    config::OscFormat::Tracker => {
+                        Box::new(sink::TrackerSink::new(client, joint_map.clone(), config.osc.bundle))
+                    }
+                    config::OscFormat::Vmc => Box::new(sink::VmcSink::new(client, joint_map.clone())),
+                }
+            }
+            config::Output::File { path } => match sink::FileSink::create(path, joint_map.clone()) {
+                Ok(file_sink) => Box::new(file_sink),
+                Err(e) => {
+                    eprintln!("error: could not open {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            },
+        };
+        sinks.push(output_sink);
+    }
 
-    let sensor_config = k4a::DeviceConfiguration {
-        //depth_mode: k4a::DepthMode::K4A_DEPTH_MODE_WFOV_2X2BINNED,
-        depth_mode: k4a::DepthMode::K4A_DEPTH_MODE_NFOV_UNBINNED,
-        ..Default::default()
+    let metrics = Arc::new(Mutex::new(metrics::Metrics::new()));
+    if let Some(addr) = config.metrics.listen {
+        if let Err(e) = metrics::serve(addr, metrics.clone()) {
+            eprintln!("Could not serve metrics on {}: {}", addr, e);
+        }
+    }
+
+    let mut source: Box<dyn SkeletonSource> = if let Some(path) = &opt.replay {
+        let replay = skeleton_file::ReplaySource::open(path).unwrap_or_else(|e| {
+            eprintln!("error: could not open {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        Box::new(replay)
+    } else if let Some(path) = &opt.playback {
+        sensor::open_playback(path, &opt, &config, metrics.clone()).unwrap_or_else(|e| {
+            eprintln!("error: could not open {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    } else if opt.synthetic {
+        Box::new(synthetic::SyntheticSource::new(synthetic::default_script(), 30))
+    } else {
+        sensor::open_devices(&config, opt.record.clone(), metrics.clone())
     };
-    let tracker_config = k4a::TrackerConfiguration {
-        gpu_device_id: 1,
-        ..Default::default()
+    if let Some(path) = &opt.dump {
+        source = match skeleton_file::RecordingSource::create(source, path) {
+            Ok(recording) => Box::new(recording),
+            Err(e) => {
+                eprintln!("error: could not create {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+    }
+    let mut selector = body_selector::BodySelector::new((&config.bodies.selection).into(), config.bodies.max);
+    let mut filters: HashMap<u32, filter::KinectJointFilter> = HashMap::new();
+    let mut send_failing = vec![false; sinks.len()];
+
+    let control = if config.osc.control {
+        // without an OSC sink, control messages still arrive on the bind address
+        let socket = control_socket.unwrap_or_else(|| UdpSocket::bind(config.osc.bind));
+        match socket.and_then(control::listen) {
+            Ok(commands) => Some(commands),
+            Err(e) => {
+                eprintln!("Could not listen for OSC control messages: {}", e);
+                None
+            }
+        }
+    } else {
+        None
     };
-    let kinect = kinect::Kinect::open_sensor(0, sensor_config, tracker_config).unwrap();
-    let mut filter = filter::KinectJointFilter::new(filter::SmoothParams::default());
-    loop {
+    let mut smoothing = config.smoothing.clone();
+    let mut recenter_requested = false;
+    let mut recenter = Vector3::zeros();
+    let mut paused = false;
+
+    let log_interval = match config.metrics.log_interval_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let mut last_log = Instant::now();
+    let failed = loop {
         if !is_running.load(Ordering::SeqCst) {
-            break;
-        }
-        profile.reload_if_updated();
-
-        let frame = kinect.next_frame().unwrap();
-        let num_bodies = frame.get_num_bodies();
-        if num_bodies == 0 {
-            osc_client.send(osc::NoBodyMessage { id: 0 }).unwrap();
-            osc_client.send(osc::NoBodyMessage { id: 1 }).unwrap();
-            osc_client.send(osc::NoBodyMessage { id: 2 }).unwrap();
-            osc_client.send(osc::NoBodyMessage { id: 3 }).unwrap();
-            osc_client.send(osc::NoBodyMessage { id: 4 }).unwrap();
+            break false;
+        }
+        for command in control.iter().flat_map(|commands| commands.try_iter()) {
+            match command {
+                control::Command::Recenter => recenter_requested = true,
+                control::Command::SetSmoothing(value) => match smoothing.set_smoothing(value) {
+                    Ok(()) => {
+                        for filter in filters.values_mut() {
+                            filter.set_params(smoothing.clone());
+                        }
+                    }
+                    Err(e) => eprintln!("Ignoring /kinectosc/filter/smoothing: {}", e),
+                },
+                control::Command::LockBody(id) => selector.set_policy(body_selector::SelectionPolicy::Locked(id)),
+                control::Command::UnlockBody => selector.set_policy((&config.bodies.selection).into()),
+                control::Command::Pause(pause) => {
+                    if pause != paused {
+                        eprintln!("{}", if pause { "Paused" } else { "Resumed" });
+                    }
+                    paused = pause;
+                }
+            }
+        }
+
+        let (bodies, captured) = match source.next_frame(FRAME_TIMEOUT_MS) {
+            Ok(frame) => (frame.bodies, Some((frame.device_timestamp_usec, frame.captured_at))),
+            Err(active_sensor::Error::Timeout) => continue,
+            Err(active_sensor::Error::End) => break false,
+            // the source is reconnecting, report every tracker as lost meanwhile
+            Err(active_sensor::Error::Disconnected) => (Vec::new(), None),
+            // devices reconnect instead, so the source is a recording or synthetic and
+            // would only fail again
+            Err(active_sensor::Error::Fatal(e)) => {
+                eprintln!("error: could not get body tracking result: {}", e);
+                break true;
+            }
+        };
+        let popped_at = Instant::now();
+        filters.retain(|id, _| bodies.iter().any(|body| body.id == *id));
+        for body in &bodies {
+            filters
+                .entry(body.id)
+                .or_insert_with(|| filter::KinectJointFilter::new(smoothing.clone()))
+                .update(&body.skeleton);
+        }
+        let assignments = selector.select(&bodies);
+        let filtered_at = Instant::now();
+        if recenter_requested {
+            if let Some(idx) = assignments.iter().flatten().next() {
+                let pelvis = &filters[&bodies[*idx].id].joints[k4a::joint_id::K4ABT_JOINT_PELVIS as usize];
+                // y points down from the sensor, keep the height
+                recenter = Vector3::new(-pelvis.filtered_position.x, 0.0, -pelvis.filtered_position.z);
+                recenter_requested = false;
+                eprintln!("Recentered");
+            }
+        }
+        if paused {
             continue;
         }
-        let skeleton: k4a::Skeleton = frame
-            .get_body_skeleton(0)
-            .unwrap();
-        filter.update(&skeleton);
-        let pelvis = &filter.joints[joint_id::K4ABT_JOINT_PELVIS as usize];
-        let message = build_message_from_filtered_joint(&profile, pelvis, 0);
-        osc_client.send(message).unwrap();
-        let ankle_left = &filter.joints[joint_id::K4ABT_JOINT_FOOT_LEFT as usize];
-        let message = build_message_from_filtered_joint(&profile, ankle_left, 1);
-        osc_client.send(message).unwrap();
-        let ankle_right = &filter.joints[joint_id::K4ABT_JOINT_FOOT_RIGHT as usize];
-        let message = build_message_from_filtered_joint(&profile, ankle_right, 2);
-        osc_client.send(message).unwrap();
-
-        let hand_left = &filter.joints[joint_id::K4ABT_JOINT_HAND_LEFT as usize];
-        let message = build_message_from_filtered_joint(&profile, hand_left, 3);
-        osc_client.send(message).unwrap();
-        let hand_right = &filter.joints[joint_id::K4ABT_JOINT_HAND_RIGHT as usize];
-        let message = build_message_from_filtered_joint(&profile, hand_right, 4);
-        osc_client.send(message).unwrap();
+        if let Some(profile) = &mut profile {
+            profile.reload_if_updated();
+        }
+        let frame = sink::Frame {
+            captured_at: captured
+                .map(|(_, captured_at)| SystemTime::now().checked_sub(captured_at.elapsed()).unwrap_or_else(SystemTime::now)),
+            bodies: assignments
+                .into_iter()
+                .map(|assignment| {
+                    assignment.map(|idx| sink::Body {
+                        id: bodies[idx].id,
+                        joints: &filters[&bodies[idx].id].joints,
+                    })
+                })
+                .collect(),
+            recenter,
+            calibration: profile.as_ref().map_or_else(Isometry3::identity, |profile| profile.calibration()),
+        };
+        for (output_sink, failing) in sinks.iter_mut().zip(&mut send_failing) {
+            report_send_result(output_sink.send(&frame), failing);
+        }
+
+        if let Some((device_timestamp_usec, captured_at)) = captured {
+            metrics.lock().unwrap().record(&metrics::FrameTiming {
+                device_timestamp_usec,
+                captured_at,
+                popped_at,
+                filtered_at,
+                sent_at: Instant::now(),
+            });
+        }
+        if let Some(interval) = log_interval {
+            if last_log.elapsed() >= interval {
+                eprintln!("Frame timing: {}", metrics.lock().unwrap().snapshot());
+                last_log = Instant::now();
+            }
+        }
+    };
+    if failed {
+        // exiting skips destructors, close the source first so that a dump is complete
+        drop(source);
+        std::process::exit(1);
+    }
+}
+
+fn report_send_result(result: io::Result<()>, failing: &mut bool) {
+    match result {
+        Ok(()) => *failing = false,
+        Err(e) => {
+            if !*failing {
+                eprintln!("Could not send frame: {}", e);
+            }
+            *failing = true;
+        }
     }
 }
 
-fn build_message_from_filtered_joint(profile: &profile_provider::ProfileProvider, joint: &filter::FilteredJoint, id: u32) -> osc::PoseMessage {
-    let wfd_rotation = profile.wfd_rotation;
-    let wfd_translation = profile.wfd_translation;
-    osc::PoseMessage {
-        id,
-        is_valid: joint.frame_count > 1,
-        wfd_rotation,
-        wfd_translation,
-        position: joint.predicted_position / 1000.0,
-        orientation: joint.raw_orientation,
-        velocity: joint.trend / 1000.0 * 30.0,
+/// Stands in for `sensor` when built without the SDKs, and fails every use of a device
+/// or recording.
+#[cfg(not(feature = "sensor"))]
+mod sensor {
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    use super::metrics::Metrics;
+    use super::skeleton_source::SkeletonSource;
+    use super::{config, Opt};
+
+    fn unsupported() -> ! {
+        eprintln!("error: built without the `sensor` feature, only --synthetic and --replay are supported");
+        std::process::exit(1);
+    }
+
+    pub fn capture_sdk_log() {}
+
+    pub fn open_playback(_: &Path, _: &Opt, _: &config::Config, _: Arc<Mutex<Metrics>>) -> io::Result<Box<dyn SkeletonSource>> {
+        unsupported()
+    }
+
+    pub fn open_devices(_: &config::Config, _: Option<PathBuf>, _: Arc<Mutex<Metrics>>) -> Box<dyn SkeletonSource> {
+        unsupported()
+    }
+
+    pub fn list_devices() {
+        unsupported()
+    }
+
+    pub fn print_color_controls(_: &config::SensorConfig) -> io::Result<()> {
+        unsupported()
     }
 }
@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+// frames the percentiles, fps and frame interval are computed over, about 10 seconds at
+// 30 fps
+const WINDOW: usize = 300;
+
+/// When each stage finished for one body tracking result.
+pub struct FrameTiming {
+    pub device_timestamp_usec: u64,
+    /// The capture was read from the sensor.
+    pub captured_at: Instant,
+    /// The body tracking result was popped from the tracker.
+    pub popped_at: Instant,
+    pub filtered_at: Instant,
+    /// The last message for the frame was sent.
+    pub sent_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1}/{:.1}/{:.1}ms", self.p50, self.p95, self.p99)
+    }
+}
+
+/// The last `WINDOW` durations, in milliseconds.
+#[derive(Default)]
+struct Window {
+    samples: VecDeque<f64>,
+}
+
+impl Window {
+    fn push(&mut self, duration: Duration) {
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration.as_secs_f64() * 1e3);
+    }
+
+    fn percentiles(&self) -> Option<Percentiles> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // nearest rank
+        let at = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(Percentiles {
+            p50: at(0.50),
+            p95: at(0.95),
+            p99: at(0.99),
+        })
+    }
+}
+
+/// A stage of reading captures ahead of the tracker, see `pipeline::Pipeline`.
+pub enum Stage {
+    /// From the capture being read to it being taken off the queue.
+    Queued,
+    /// Blocked in `enqueue_capture` while the tracker queue is full.
+    Enqueue,
+    /// From `enqueue_capture` returning to the result being popped.
+    Inference,
+}
+
+/// Captures read ahead of the tracker, summed over every device, and how long each stage
+/// took for the last `WINDOW` of them.
+#[derive(Default)]
+struct PipelineWindows {
+    captured: u64,
+    dropped: u64,
+    queued: Window,
+    enqueue: Window,
+    inference: Window,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineSnapshot {
+    pub captured: u64,
+    /// Captures dropped from a full queue before the tracker got to them.
+    pub dropped: u64,
+    pub queued_ms: Option<Percentiles>,
+    pub enqueue_ms: Option<Percentiles>,
+    pub inference_ms: Option<Percentiles>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub frames: u64,
+    /// Frames the sensor produced but that never made it out, from gaps in device timestamps.
+    pub dropped: u64,
+    pub fps: f64,
+    /// Capture read to tracking result popped.
+    pub tracking_ms: Option<Percentiles>,
+    pub filter_ms: Option<Percentiles>,
+    pub send_ms: Option<Percentiles>,
+    /// Capture read to last message sent, i.e. how stale poses are when they leave.
+    pub total_ms: Option<Percentiles>,
+    /// Present when captures are read ahead of the tracker.
+    pub pipeline: Option<PipelineSnapshot>,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |p: &Option<Percentiles>| p.map_or_else(|| "-".to_string(), |p| p.to_string());
+        write!(
+            f,
+            "{} frames, {} dropped, {:.1} fps, p50/p95/p99 tracking {}, filter {}, send {}, total {}",
+            self.frames,
+            self.dropped,
+            self.fps,
+            show(&self.tracking_ms),
+            show(&self.filter_ms),
+            show(&self.send_ms),
+            show(&self.total_ms),
+        )?;
+        if let Some(pipeline) = &self.pipeline {
+            write!(
+                f,
+                "; pipeline {} captured, {} dropped, p50/p95/p99 queued {}, enqueue {}, inference {}",
+                pipeline.captured,
+                pipeline.dropped,
+                show(&pipeline.queued_ms),
+                show(&pipeline.enqueue_ms),
+                show(&pipeline.inference_ms),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Metrics {
+    // the last `WINDOW` gaps between device timestamps, in microseconds
+    gaps_usec: VecDeque<u64>,
+    frames: u64,
+    dropped: u64,
+    last_device_timestamp_usec: Option<u64>,
+    sent_at: VecDeque<Instant>,
+    tracking: Window,
+    filter: Window,
+    send: Window,
+    total: Window,
+    pipeline: Option<PipelineWindows>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            gaps_usec: VecDeque::with_capacity(WINDOW),
+            frames: 0,
+            dropped: 0,
+            last_device_timestamp_usec: None,
+            sent_at: VecDeque::with_capacity(WINDOW),
+            tracking: Window::default(),
+            filter: Window::default(),
+            send: Window::default(),
+            total: Window::default(),
+            pipeline: None,
+        }
+    }
+
+    /// Call for every capture read ahead of the tracker, with whether the queue was full
+    /// and dropped an older one for it.
+    pub fn record_capture(&mut self, dropped: bool) {
+        let pipeline = self.pipeline.get_or_insert_with(PipelineWindows::default);
+        pipeline.captured += 1;
+        if dropped {
+            pipeline.dropped += 1;
+        }
+    }
+
+    pub fn record_stage(&mut self, stage: Stage, duration: Duration) {
+        let pipeline = self.pipeline.get_or_insert_with(PipelineWindows::default);
+        match stage {
+            Stage::Queued => pipeline.queued.push(duration),
+            Stage::Enqueue => pipeline.enqueue.push(duration),
+            Stage::Inference => pipeline.inference.push(duration),
+        }
+    }
+
+    pub fn record(&mut self, timing: &FrameTiming) {
+        self.frames += 1;
+        if let Some(last) = self.last_device_timestamp_usec {
+            // zero when a playback loops around
+            let gap = timing.device_timestamp_usec.saturating_sub(last);
+            if gap > 0 {
+                if self.gaps_usec.len() == WINDOW {
+                    self.gaps_usec.pop_front();
+                }
+                self.gaps_usec.push_back(gap);
+                // the sensor's frame interval, as long as it sometimes gets two frames in a row
+                let interval = *self.gaps_usec.iter().min().unwrap();
+                // a gap of more than 1.5 intervals means frames were skipped
+                if gap * 2 > interval * 3 {
+                    let missing = (gap + interval / 2) / interval - 1;
+                    self.dropped += missing;
+                }
+            }
+        }
+        self.last_device_timestamp_usec = Some(timing.device_timestamp_usec);
+        if self.sent_at.len() == WINDOW {
+            self.sent_at.pop_front();
+        }
+        self.sent_at.push_back(timing.sent_at);
+        self.tracking.push(timing.popped_at.saturating_duration_since(timing.captured_at));
+        self.filter.push(timing.filtered_at.saturating_duration_since(timing.popped_at));
+        self.send.push(timing.sent_at.saturating_duration_since(timing.filtered_at));
+        self.total.push(timing.sent_at.saturating_duration_since(timing.captured_at));
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let fps = match (self.sent_at.front(), self.sent_at.back()) {
+            (Some(first), Some(last)) if last > first => {
+                (self.sent_at.len() - 1) as f64 / (*last - *first).as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        Snapshot {
+            frames: self.frames,
+            dropped: self.dropped,
+            fps,
+            tracking_ms: self.tracking.percentiles(),
+            filter_ms: self.filter.percentiles(),
+            send_ms: self.send.percentiles(),
+            total_ms: self.total.percentiles(),
+            pipeline: self.pipeline.as_ref().map(|pipeline| PipelineSnapshot {
+                captured: pipeline.captured,
+                dropped: pipeline.dropped,
+                queued_ms: pipeline.queued.percentiles(),
+                enqueue_ms: pipeline.enqueue.percentiles(),
+                inference_ms: pipeline.inference.percentiles(),
+            }),
+        }
+    }
+}
+
+/// Answers every connection to `addr` with the current snapshot as JSON over HTTP, so
+/// that `curl` or a browser can read it.
+pub fn serve(addr: SocketAddr, metrics: Arc<Mutex<Metrics>>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let snapshot = metrics.lock().unwrap().snapshot();
+            let body = serde_json::to_string(&snapshot).unwrap();
+            // the request itself does not matter, read some of it so that clients do not
+            // see a reset connection
+            let _ = stream.set_read_timeout(Some(Duration::from_millis(100)));
+            let _ = stream.read(&mut [0u8; 1024]);
+            let _ = write!(
+                stream,
+                "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(start: Instant, device_timestamp_usec: u64, ms: [u64; 3]) -> FrameTiming {
+        let popped_at = start + Duration::from_millis(ms[0]);
+        let filtered_at = popped_at + Duration::from_millis(ms[1]);
+        FrameTiming {
+            device_timestamp_usec,
+            captured_at: start,
+            popped_at,
+            filtered_at,
+            sent_at: filtered_at + Duration::from_millis(ms[2]),
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut window = Window::default();
+        for ms in 1..=100 {
+            window.push(Duration::from_millis(ms));
+        }
+        let p = window.percentiles().unwrap();
+        assert_eq!(p, Percentiles { p50: 50.0, p95: 95.0, p99: 99.0 });
+        assert!(Window::default().percentiles().is_none());
+    }
+
+    #[test]
+    fn test_dropped_frames() {
+        let mut metrics = Metrics::new();
+        let start = Instant::now();
+        // a gap of four intervals skips three frames, a slightly late frame skips none
+        for &timestamp in &[0, 33_333, 166_665, 200_500] {
+            metrics.record(&timing(start, timestamp, [20, 1, 2]));
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.frames, 4);
+        assert_eq!(snapshot.dropped, 3);
+        assert_eq!(snapshot.total_ms.unwrap().p50, 23.0);
+        assert!(snapshot.pipeline.is_none());
+
+        // the interval follows the sensor, here at 15 fps
+        let mut metrics = Metrics::new();
+        for &timestamp in &[0, 66_666, 133_333, 333_333] {
+            metrics.record(&timing(start, timestamp, [20, 1, 2]));
+        }
+        assert_eq!(metrics.snapshot().dropped, 2);
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let mut metrics = Metrics::new();
+        metrics.record_capture(false);
+        metrics.record_capture(true);
+        metrics.record_stage(Stage::Inference, Duration::from_millis(30));
+        let pipeline = metrics.snapshot().pipeline.unwrap();
+        assert_eq!((2, 1), (pipeline.captured, pipeline.dropped));
+        assert_eq!(30.0, pipeline.inference_ms.unwrap().p50);
+        assert!(pipeline.queued_ms.is_none());
+    }
+}
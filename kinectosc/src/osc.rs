@@ -1,31 +1,57 @@
+use std::fmt;
 use std::io::{self, Write};
-use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use nalgebra::{Point3, Translation3, UnitQuaternion, Vector3};
 
+use super::transport::{self, Destination, Transport};
+
 pub struct Client {
     socket: UdpSocket,
-    target: SocketAddr,
+    transports: Vec<Box<dyn Transport>>,
     buf: Vec<u8>,
 }
 
 impl Client {
-    pub fn new<A: ToSocketAddrs>(bind: A, target: SocketAddr) -> io::Result<Self> {
+    pub fn new<A: ToSocketAddrs>(bind: A, destinations: &[Destination]) -> io::Result<Self> {
         let socket = UdpSocket::bind(bind)?;
+        let transports = destinations
+            .iter()
+            .map(|destination| transport::open(destination, &socket))
+            .collect::<io::Result<_>>()?;
         let client = Client {
             socket,
-            target,
+            transports,
             buf: Vec::with_capacity(1024),
         };
         Ok(client)
     }
 
+    /// Sends to every destination, even after one fails. Returns the first error.
     pub fn send<M: Message>(&mut self, message: M) -> io::Result<()> {
+        self.buf.clear();
         let writer = OscPadWriter::new(&mut self.buf);
         message.encode(writer)?;
-        self.socket.send_to(&self.buf, self.target)?;
-        self.buf.clear();
-        Ok(())
+        let mut result = Ok(());
+        for transport in self.transports.iter_mut() {
+            let sent = transport.send(&self.buf);
+            if result.is_ok() {
+                result = sent;
+            }
+        }
+        result
+    }
+
+    /// The socket messages are sent from, to receive replies or commands on.
+    pub fn try_clone_socket(&self) -> io::Result<UdpSocket> {
+        self.socket.try_clone()
+    }
+
+    /// Sends every message of the bundle in one datagram, so that receivers see all of
+    /// them or none.
+    pub fn send_bundle(&mut self, bundle: &Bundle) -> io::Result<()> {
+        self.send(bundle)
     }
 }
 
@@ -33,6 +59,191 @@ pub trait Message {
     fn encode<W: Write>(&self, w: OscPadWriter<W>) -> io::Result<usize>;
 }
 
+impl<M: Message> Message for &M {
+    fn encode<W: Write>(&self, w: OscPadWriter<W>) -> io::Result<usize> {
+        (*self).encode(w)
+    }
+}
+
+/// An NTP timestamp: seconds since 1900 and fractions of a second in 1/2^32 s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeTag {
+    pub seconds: u32,
+    pub fraction: u32,
+}
+
+// seconds from 1900-01-01 to 1970-01-01
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+impl TimeTag {
+    /// Tells receivers to act on a bundle as soon as it arrives.
+    pub const IMMEDIATELY: TimeTag = TimeTag { seconds: 0, fraction: 1 };
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        TimeTag {
+            seconds: (since_epoch.as_secs() + NTP_UNIX_OFFSET) as u32,
+            fraction: (((since_epoch.subsec_nanos() as u64) << 32) / 1_000_000_000) as u32,
+        }
+    }
+
+    pub fn is_immediately(self) -> bool {
+        self == TimeTag::IMMEDIATELY
+    }
+
+    pub fn is_before(self, other: TimeTag) -> bool {
+        (self.seconds, self.fraction) < (other.seconds, other.fraction)
+    }
+
+    pub fn to_system_time(self) -> SystemTime {
+        let nanos = ((self.fraction as u64) * 1_000_000_000) >> 32;
+        let seconds = (self.seconds as u64).saturating_sub(NTP_UNIX_OFFSET);
+        UNIX_EPOCH + Duration::new(seconds, nanos as u32)
+    }
+}
+
+/// A typed OSC argument, covering the OSC 1.0 required and optional types and the
+/// OSC 1.1 additions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+    Long(i64),
+    Double(f64),
+    TimeTag(TimeTag),
+    Symbol(String),
+    Char(char),
+    /// Red, green, blue and alpha, one byte each from the most significant.
+    Rgba(u32),
+    /// Port id, status byte, data1, data2.
+    Midi([u8; 4]),
+    Bool(bool),
+    Nil,
+    Impulse,
+    Array(Vec<Argument>),
+}
+
+impl Argument {
+    fn write_type_tag(&self, type_tags: &mut String) {
+        match self {
+            Argument::Int(_) => type_tags.push('i'),
+            Argument::Float(_) => type_tags.push('f'),
+            Argument::String(_) => type_tags.push('s'),
+            Argument::Blob(_) => type_tags.push('b'),
+            Argument::Long(_) => type_tags.push('h'),
+            Argument::Double(_) => type_tags.push('d'),
+            Argument::TimeTag(_) => type_tags.push('t'),
+            Argument::Symbol(_) => type_tags.push('S'),
+            Argument::Char(_) => type_tags.push('c'),
+            Argument::Rgba(_) => type_tags.push('r'),
+            Argument::Midi(_) => type_tags.push('m'),
+            Argument::Bool(true) => type_tags.push('T'),
+            Argument::Bool(false) => type_tags.push('F'),
+            Argument::Nil => type_tags.push('N'),
+            Argument::Impulse => type_tags.push('I'),
+            Argument::Array(arguments) => {
+                type_tags.push('[');
+                for argument in arguments {
+                    argument.write_type_tag(type_tags);
+                }
+                type_tags.push(']');
+            }
+        }
+    }
+
+    fn write_value<W: Write>(&self, w: &mut OscPadWriter<W>) -> io::Result<usize> {
+        match self {
+            Argument::Int(i) => w.write_int(*i),
+            Argument::Float(f) => w.write_float(*f),
+            Argument::String(s) | Argument::Symbol(s) => w.write_string(s),
+            Argument::Blob(b) => w.write_blob(b),
+            Argument::Long(h) => w.write_long(*h),
+            Argument::Double(d) => w.write_double(*d),
+            Argument::TimeTag(t) => w.write_timetag(*t),
+            Argument::Char(c) => w.write_int(*c as i32),
+            Argument::Rgba(r) => w.write_int(*r as i32),
+            Argument::Midi(m) => w.write_int(i32::from_be_bytes(*m)),
+            Argument::Bool(_) | Argument::Nil | Argument::Impulse => Ok(0),
+            Argument::Array(arguments) => {
+                let mut len = 0;
+                for argument in arguments {
+                    len += argument.write_value(w)?;
+                }
+                Ok(len)
+            }
+        }
+    }
+}
+
+/// A message with any address and arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub arguments: Vec<Argument>,
+}
+
+impl Message for OscMessage {
+    fn encode<W: Write>(&self, mut w: OscPadWriter<W>) -> io::Result<usize> {
+        let mut type_tags = String::from(",");
+        for argument in &self.arguments {
+            argument.write_type_tag(&mut type_tags);
+        }
+        let mut len = 0;
+        len += w.write_string(&self.address)?;
+        len += w.write_string(&type_tags)?;
+        for argument in &self.arguments {
+            len += argument.write_value(&mut w)?;
+        }
+        Ok(len)
+    }
+}
+
+/// Messages, or nested bundles, to be acted on together at `timetag`.
+pub struct Bundle {
+    timetag: TimeTag,
+    // size-prefixed elements
+    content: Vec<u8>,
+}
+
+impl Bundle {
+    pub fn new(timetag: TimeTag) -> Self {
+        Bundle {
+            timetag,
+            content: Vec::new(),
+        }
+    }
+
+    /// Adds a message, or a bundle, which must not be later than this one.
+    pub fn push<M: Message>(&mut self, element: M) -> io::Result<()> {
+        let start = self.content.len();
+        self.content.extend_from_slice(&[0; 4]);
+        let len = element.encode(OscPadWriter::new(&mut self.content))?;
+        self.content[start..start + 4].copy_from_slice(&(len as i32).to_be_bytes());
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    pub fn clear(&mut self, timetag: TimeTag) {
+        self.timetag = timetag;
+        self.content.clear();
+    }
+}
+
+impl Message for Bundle {
+    fn encode<W: Write>(&self, mut w: OscPadWriter<W>) -> io::Result<usize> {
+        let mut len = 0;
+        len += w.write_string("#bundle")?;
+        len += w.write_timetag(self.timetag)?;
+        w.inner.write_all(&self.content)?;
+        Ok(len + self.content.len())
+    }
+}
+
 pub struct PoseMessage {
     pub id: u32,
     pub is_valid: bool,
@@ -124,6 +335,405 @@ where
         self.inner.write_all(&d.to_be_bytes())?;
         Ok(8)
     }
+
+    pub fn write_float(&mut self, f: f32) -> io::Result<usize> {
+        self.inner.write_all(&f.to_be_bytes())?;
+        Ok(4)
+    }
+
+    pub fn write_long(&mut self, h: i64) -> io::Result<usize> {
+        self.inner.write_all(&h.to_be_bytes())?;
+        Ok(8)
+    }
+
+    pub fn write_timetag(&mut self, t: TimeTag) -> io::Result<usize> {
+        self.inner.write_all(&t.seconds.to_be_bytes())?;
+        self.inner.write_all(&t.fraction.to_be_bytes())?;
+        Ok(8)
+    }
+
+    /// Size, then the bytes padded to a multiple of 4.
+    pub fn write_blob(&mut self, b: &[u8]) -> io::Result<usize> {
+        let pad_len = (4 - (b.len() & 0b11)) & 0b11;
+        self.write_int(b.len() as i32)?;
+        self.inner.write_all(b)?;
+        self.inner.write_all(&[0u8; 4][..pad_len])?;
+        Ok(4 + b.len() + pad_len)
+    }
+}
+
+/// A received packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    Message(OscMessage),
+    Bundle(TimeTag, Vec<Packet>),
+}
+
+impl Packet {
+    /// The messages of the packet, including those in nested bundles, in order.
+    pub fn into_messages(self) -> Vec<OscMessage> {
+        match self {
+            Packet::Message(message) => vec![message],
+            Packet::Bundle(_, elements) => elements.into_iter().flat_map(Packet::into_messages).collect(),
+        }
+    }
+}
+
+/// Why a packet could not be decoded, and at which byte offset into it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// Packets and bundle elements are made of 4 byte words.
+    Misaligned { offset: usize, size: usize },
+    Truncated { offset: usize, needed: usize },
+    UnterminatedString { offset: usize },
+    NotUtf8 { offset: usize },
+    NonZeroPadding { offset: usize },
+    BadAddress { offset: usize, address: String },
+    BadTypeTags { offset: usize, type_tags: String },
+    UnknownTypeTag { offset: usize, tag: char },
+    InvalidChar { offset: usize, value: i32 },
+    NegativeSize { offset: usize, size: i32 },
+    /// A bundle within a bundle must not be due earlier than the bundle it is in.
+    EarlierTimeTag { offset: usize },
+    /// Bundles or arrays nested deeper than `MAX_NESTING`.
+    TooDeep { offset: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed OSC packet: ")?;
+        match self {
+            DecodeError::Misaligned { offset, size } => {
+                write!(f, "size {} at offset {} is not a multiple of 4", size, offset)
+            }
+            DecodeError::Truncated { offset, needed } => {
+                write!(f, "{} more bytes needed at offset {}", needed, offset)
+            }
+            DecodeError::UnterminatedString { offset } => write!(f, "unterminated string at offset {}", offset),
+            DecodeError::NotUtf8 { offset } => write!(f, "string at offset {} is not UTF-8", offset),
+            DecodeError::NonZeroPadding { offset } => write!(f, "non-zero padding at offset {}", offset),
+            DecodeError::BadAddress { offset, address } => {
+                write!(f, "address {:?} at offset {} does not start with '/'", address, offset)
+            }
+            DecodeError::BadTypeTags { offset, type_tags } => {
+                write!(f, "type tags {:?} at offset {} are not well formed", type_tags, offset)
+            }
+            DecodeError::UnknownTypeTag { offset, tag } => {
+                write!(f, "unknown type tag {:?} for the argument at offset {}", tag, offset)
+            }
+            DecodeError::InvalidChar { offset, value } => {
+                write!(f, "{} at offset {} is not a character", value, offset)
+            }
+            DecodeError::NegativeSize { offset, size } => write!(f, "negative size {} at offset {}", size, offset),
+            DecodeError::EarlierTimeTag { offset } => {
+                write!(f, "bundle at offset {} is due before the bundle it is in", offset)
+            }
+            DecodeError::TooDeep { offset } => {
+                write!(f, "nested more than {} levels deep at offset {}", MAX_NESTING, offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// How deep bundles may be nested in bundles, and arrays in arrays, when decoding.
+const MAX_NESTING: usize = 16;
+
+/// Decodes a whole packet, rejecting anything that does not strictly follow OSC 1.0/1.1:
+/// misaligned sizes, non-zero padding, malformed type tags and trailing bytes.
+pub fn decode(packet: &[u8]) -> Result<Packet, DecodeError> {
+    decode_element(packet, 0, None, 0)
+}
+
+/// `offset` is where `element` starts in the outermost packet, `due` the time tag of the
+/// bundle it is in and `depth` the number of bundles it is in.
+fn decode_element(
+    element: &[u8],
+    offset: usize,
+    due: Option<TimeTag>,
+    depth: usize,
+) -> Result<Packet, DecodeError> {
+    if element.len() & 0b11 != 0 {
+        return Err(DecodeError::Misaligned { offset, size: element.len() });
+    }
+    let mut r = OscReader { buf: element, offset, pos: 0 };
+    if element.starts_with(b"#bundle\0") {
+        if depth == MAX_NESTING {
+            return Err(DecodeError::TooDeep { offset });
+        }
+        r.pos = 8;
+        let timetag_offset = r.offset();
+        let timetag = r.read_timetag()?;
+        if due.is_some_and(|due| !due.is_immediately() && timetag.is_before(due)) {
+            return Err(DecodeError::EarlierTimeTag { offset: timetag_offset });
+        }
+        let mut elements = Vec::new();
+        while !r.is_empty() {
+            let size_offset = r.offset();
+            let size = r.read_int()?;
+            if size < 0 {
+                return Err(DecodeError::NegativeSize { offset: size_offset, size });
+            }
+            let element_offset = r.offset();
+            let element = r.take(size as usize)?;
+            elements.push(decode_element(element, element_offset, Some(timetag), depth + 1)?);
+        }
+        return Ok(Packet::Bundle(timetag, elements));
+    }
+
+    let address_offset = r.offset();
+    let address = r.read_string()?;
+    if !address.starts_with('/') {
+        return Err(DecodeError::BadAddress { offset: address_offset, address });
+    }
+    // OSC 1.0 allows omitting the type tags of a message without arguments
+    let arguments = if r.is_empty() {
+        Vec::new()
+    } else {
+        let type_tags_offset = r.offset();
+        let type_tags = r.read_string()?;
+        if !type_tags.starts_with(',') {
+            return Err(DecodeError::BadTypeTags { offset: type_tags_offset, type_tags });
+        }
+        match array_depth(&type_tags) {
+            Some(depth) if depth > MAX_NESTING => return Err(DecodeError::TooDeep { offset: type_tags_offset }),
+            Some(_) => {}
+            None => return Err(DecodeError::BadTypeTags { offset: type_tags_offset, type_tags }),
+        }
+        let arguments = r.read_arguments(&mut type_tags[1..].chars())?;
+        if !r.is_empty() {
+            // arguments the type tags do not account for
+            return Err(DecodeError::BadTypeTags { offset: type_tags_offset, type_tags });
+        }
+        arguments
+    };
+    Ok(Packet::Message(OscMessage { address, arguments }))
+}
+
+/// How deep the arrays of `type_tags` are nested, or `None` if the brackets do not
+/// balance.
+fn array_depth(type_tags: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut max_depth = 0;
+    for tag in type_tags.chars() {
+        match tag {
+            '[' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            ']' if depth == 0 => return None,
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    if depth == 0 {
+        Some(max_depth)
+    } else {
+        None
+    }
+}
+
+struct OscReader<'a> {
+    buf: &'a [u8],
+    // of `buf` in the outermost packet
+    offset: usize,
+    pos: usize,
+}
+
+impl<'a> OscReader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    fn offset(&self) -> usize {
+        self.offset + self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let available = self.buf.len() - self.pos;
+        if len > available {
+            return Err(DecodeError::Truncated { offset: self.offset(), needed: len - available });
+        }
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    /// Skips the padding up to the next multiple of 4 after `len` bytes.
+    fn take_padding(&mut self, len: usize) -> Result<(), DecodeError> {
+        let offset = self.offset();
+        let padding = self.take((4 - (len & 0b11)) & 0b11)?;
+        if padding.iter().any(|b| *b != 0) {
+            return Err(DecodeError::NonZeroPadding { offset });
+        }
+        Ok(())
+    }
+
+    fn read_string(&mut self) -> Result<String, DecodeError> {
+        let offset = self.offset();
+        let len = match self.buf[self.pos..].iter().position(|b| *b == 0) {
+            Some(len) => len,
+            None => return Err(DecodeError::UnterminatedString { offset }),
+        };
+        let bytes = self.take(len + 1)?;
+        let s = std::str::from_utf8(&bytes[..len]).map_err(|_| DecodeError::NotUtf8 { offset })?;
+        self.take_padding(len + 1)?;
+        Ok(s.to_owned())
+    }
+
+    fn read_int(&mut self) -> Result<i32, DecodeError> {
+        Ok(i32::from_be_bytes(self.take_array()?))
+    }
+
+    fn read_timetag(&mut self) -> Result<TimeTag, DecodeError> {
+        let seconds = u32::from_be_bytes(self.take_array()?);
+        let fraction = u32::from_be_bytes(self.take_array()?);
+        Ok(TimeTag { seconds, fraction })
+    }
+
+    fn read_blob(&mut self) -> Result<Vec<u8>, DecodeError> {
+        let offset = self.offset();
+        let size = self.read_int()?;
+        if size < 0 {
+            return Err(DecodeError::NegativeSize { offset, size });
+        }
+        let blob = self.take(size as usize)?.to_vec();
+        self.take_padding(blob.len())?;
+        Ok(blob)
+    }
+
+    /// Reads an argument for each type tag, up to the end or the `]` closing the array.
+    /// The brackets are known to balance and to nest no deeper than `MAX_NESTING`.
+    fn read_arguments(&mut self, type_tags: &mut std::str::Chars) -> Result<Vec<Argument>, DecodeError> {
+        let mut arguments = Vec::new();
+        loop {
+            let offset = self.offset();
+            let argument = match type_tags.next() {
+                None | Some(']') => return Ok(arguments),
+                Some('[') => Argument::Array(self.read_arguments(type_tags)?),
+                Some('i') => Argument::Int(self.read_int()?),
+                Some('f') => Argument::Float(f32::from_be_bytes(self.take_array()?)),
+                Some('s') => Argument::String(self.read_string()?),
+                Some('S') => Argument::Symbol(self.read_string()?),
+                Some('b') => Argument::Blob(self.read_blob()?),
+                Some('h') => Argument::Long(i64::from_be_bytes(self.take_array()?)),
+                Some('d') => Argument::Double(f64::from_be_bytes(self.take_array()?)),
+                Some('t') => Argument::TimeTag(self.read_timetag()?),
+                Some('c') => {
+                    let value = self.read_int()?;
+                    match char::from_u32(value as u32) {
+                        Some(c) => Argument::Char(c),
+                        None => return Err(DecodeError::InvalidChar { offset, value }),
+                    }
+                }
+                Some('r') => Argument::Rgba(u32::from_be_bytes(self.take_array()?)),
+                Some('m') => Argument::Midi(self.take_array()?),
+                Some('T') => Argument::Bool(true),
+                Some('F') => Argument::Bool(false),
+                Some('N') => Argument::Nil,
+                Some('I') => Argument::Impulse,
+                Some(tag) => return Err(DecodeError::UnknownTypeTag { offset, tag }),
+            };
+            arguments.push(argument);
+        }
+    }
+}
+
+/// Whether the address pattern of a received message matches `address`. Supports `?`,
+/// `*`, `[abc]`, `[a-z]`, `[!abc]` and `{foo,bar}`, none of which match a `/`.
+pub fn pattern_matches(pattern: &str, address: &str) -> bool {
+    match_bytes(pattern.as_bytes(), address.as_bytes())
+}
+
+/// Walks the pattern once, keeping every position in the address that the pattern so far
+/// can end at. Neither time nor stack depth grow with the number of wildcards beyond that.
+fn match_bytes(mut pattern: &[u8], address: &[u8]) -> bool {
+    let mut reachable = vec![false; address.len() + 1];
+    let mut next = vec![false; address.len() + 1];
+    reachable[0] = true;
+    while let Some((first, rest)) = pattern.split_first() {
+        next.iter_mut().for_each(|n| *n = false);
+        pattern = rest;
+        match first {
+            b'*' => {
+                let mut matching = false;
+                for (i, r) in reachable.iter().enumerate() {
+                    matching |= *r;
+                    next[i] = matching;
+                    if address.get(i) == Some(&b'/') {
+                        matching = false;
+                    }
+                }
+            }
+            b'?' => {
+                for (i, c) in address.iter().enumerate() {
+                    next[i + 1] = reachable[i] && *c != b'/';
+                }
+            }
+            b'[' => {
+                let end = match rest.iter().position(|c| *c == b']') {
+                    Some(end) => end,
+                    None => return false,
+                };
+                for (i, c) in address.iter().enumerate() {
+                    next[i + 1] = reachable[i] && *c != b'/' && in_set(&rest[..end], *c);
+                }
+                pattern = &rest[end + 1..];
+            }
+            b'{' => {
+                let end = match rest.iter().position(|c| *c == b'}') {
+                    Some(end) => end,
+                    None => return false,
+                };
+                for i in (0..=address.len()).filter(|i| reachable[*i]) {
+                    for alternative in rest[..end].split(|c| *c == b',') {
+                        if address[i..].starts_with(alternative) {
+                            next[i + alternative.len()] = true;
+                        }
+                    }
+                }
+                pattern = &rest[end + 1..];
+            }
+            c => {
+                for (i, a) in address.iter().enumerate() {
+                    next[i + 1] = reachable[i] && a == c;
+                }
+            }
+        }
+        std::mem::swap(&mut reachable, &mut next);
+        if !reachable.contains(&true) {
+            return false;
+        }
+    }
+    reachable[address.len()]
+}
+
+fn in_set(set: &[u8], c: u8) -> bool {
+    let (negated, mut set) = match set.split_first() {
+        Some((b'!', set)) => (true, set),
+        _ => (false, set),
+    };
+    let mut found = false;
+    while let Some((first, rest)) = set.split_first() {
+        match rest {
+            [b'-', last, rest @ ..] => {
+                found |= (*first..=*last).contains(&c);
+                set = rest;
+            }
+            _ => {
+                found |= *first == c;
+                set = rest;
+            }
+        }
+    }
+    found != negated
 }
 
 #[cfg(test)]
@@ -137,4 +747,239 @@ mod tests {
         w.write_string("abc").unwrap();
         assert_eq!(vec![0x61, 0x62, 0x63, 0x00], buf);
     }
+
+    #[test]
+    fn test_encode_bundle() {
+        let message = OscMessage {
+            address: "/a".into(),
+            arguments: vec![
+                Argument::Blob(vec![1, 2, 3, 4, 5]),
+                Argument::Array(vec![Argument::Bool(true), Argument::Nil, Argument::Float(1.0)]),
+            ],
+        };
+        let mut bundle = Bundle::new(TimeTag { seconds: 1, fraction: 2 });
+        bundle.push(&message).unwrap();
+        let mut buf = Vec::new();
+        let len = bundle.encode(OscPadWriter::new(&mut buf)).unwrap();
+        assert_eq!(buf.len(), len);
+        let mut expected = b"#bundle\0\0\0\0\x01\0\0\0\x02\0\0\0\x1c".to_vec();
+        expected.extend_from_slice(b"/a\0\0,b[TNf]\0");
+        expected.extend_from_slice(&[0, 0, 0, 5, 1, 2, 3, 4, 5, 0, 0, 0, 0x3f, 0x80, 0, 0]);
+        assert_eq!(expected, buf);
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_600_000_000_500);
+        let timetag = TimeTag::from_system_time(time);
+        assert_eq!(1_600_000_000 + 2_208_988_800, timetag.seconds as u64);
+        assert_eq!(1 << 31, timetag.fraction);
+        assert_eq!(time, timetag.to_system_time());
+
+        assert_eq!(Packet::Bundle(TimeTag { seconds: 1, fraction: 2 }, vec![Packet::Message(message)]), decode(&buf).unwrap());
+    }
+
+    fn encode<M: Message>(message: M) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let len = message.encode(OscPadWriter::new(&mut buf)).unwrap();
+        assert_eq!(buf.len(), len);
+        buf
+    }
+
+    #[test]
+    fn test_round_trip_pose_messages() {
+        let pose = PoseMessage {
+            id: 7,
+            is_valid: true,
+            wfd_rotation: UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3),
+            wfd_translation: Translation3::new(1., 2., 3.),
+            position: Point3::new(0.5, -1., 2.),
+            orientation: UnitQuaternion::from_euler_angles(-0.3, 0., 1.),
+            velocity: Vector3::new(0.1, 0., -0.1),
+        };
+        let mut bundle = Bundle::new(TimeTag::IMMEDIATELY);
+        bundle.push(&pose).unwrap();
+        bundle.push(NoBodyMessage { id: 8 }).unwrap();
+        let messages = decode(&encode(&bundle)).unwrap().into_messages();
+
+        let mut expected = vec![Argument::Int(7), Argument::Int(1)];
+        let r = &pose.wfd_rotation;
+        let t = &pose.wfd_translation;
+        let o = &pose.orientation;
+        let v = &pose.velocity;
+        let p = &pose.position;
+        for d in &[r.w, r.i, r.j, r.k, t.x, t.y, t.z, p.x, p.y, p.z, o.w, o.i, o.j, o.k, v.x, v.y, v.z] {
+            expected.push(Argument::Double(*d));
+        }
+        assert_eq!(
+            vec![
+                OscMessage { address: "/Tracker/Pose".into(), arguments: expected },
+                OscMessage { address: "/Tracker/NoBody".into(), arguments: vec![Argument::Int(8)] },
+            ],
+            messages
+        );
+    }
+
+    // xorshift, so that failures reproduce
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> usize {
+            (self.next() % n) as usize
+        }
+
+        fn string(&mut self) -> String {
+            (0..self.below(10)).map(|_| (b'a' + self.below(26) as u8) as char).collect()
+        }
+
+        fn argument(&mut self, depth: u32) -> Argument {
+            match self.below(if depth < 2 { 16 } else { 15 }) {
+                0 => Argument::Int(self.next() as i32),
+                1 => Argument::Float(self.below(100_000) as f32 / 7.),
+                2 => Argument::String(self.string()),
+                3 => Argument::Blob((0..self.below(10)).map(|_| self.next() as u8).collect()),
+                4 => Argument::Long(self.next() as i64),
+                5 => Argument::Double(self.below(100_000) as f64 / 7.),
+                6 => Argument::TimeTag(TimeTag { seconds: self.next() as u32, fraction: self.next() as u32 }),
+                7 => Argument::Symbol(self.string()),
+                8 => Argument::Char((b' ' + self.below(95) as u8) as char),
+                9 => Argument::Rgba(self.next() as u32),
+                10 => Argument::Midi((self.next() as u32).to_be_bytes()),
+                11 => Argument::Bool(self.below(2) == 0),
+                12 => Argument::Nil,
+                13 => Argument::Impulse,
+                14 => Argument::Int(-1),
+                _ => Argument::Array((0..self.below(4)).map(|_| self.argument(depth + 1)).collect()),
+            }
+        }
+
+        fn message(&mut self) -> OscMessage {
+            OscMessage {
+                address: format!("/{}/{}", self.string(), self.string()),
+                arguments: (0..self.below(6)).map(|_| self.argument(0)).collect(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_random() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..1000 {
+            let message = rng.message();
+            assert_eq!(Packet::Message(message.clone()), decode(&encode(&message)).unwrap());
+
+            let timetag = TimeTag { seconds: rng.next() as u32, fraction: rng.next() as u32 };
+            let mut bundle = Bundle::new(timetag);
+            let messages: Vec<OscMessage> = (0..rng.below(4)).map(|_| rng.message()).collect();
+            for message in &messages {
+                bundle.push(message).unwrap();
+            }
+            let elements = messages.into_iter().map(Packet::Message).collect();
+            assert_eq!(Packet::Bundle(timetag, elements), decode(&encode(&bundle)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_decode_garbage() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let mut bundle = Bundle::new(TimeTag::IMMEDIATELY);
+            bundle.push(rng.message()).unwrap();
+            let mut packet = encode(&bundle);
+            // decoding must fail or succeed, never panic
+            match rng.below(3) {
+                0 => packet.truncate(rng.below(packet.len() as u64)),
+                1 => {
+                    let index = rng.below(packet.len() as u64);
+                    packet[index] = rng.next() as u8;
+                }
+                _ => packet = (0..rng.below(64)).map(|_| rng.next() as u8).collect(),
+            }
+            let _ = decode(&packet);
+        }
+    }
+
+    #[test]
+    fn test_reject_malformed() {
+        assert_eq!(Err(DecodeError::Misaligned { offset: 0, size: 3 }), decode(b"/a\0"));
+        assert_eq!(Err(DecodeError::NonZeroPadding { offset: 3 }), decode(b"/a\0x"));
+        assert_eq!(
+            Err(DecodeError::BadAddress { offset: 0, address: "a".into() }),
+            decode(b"a\0\0\0")
+        );
+        assert_eq!(Err(DecodeError::Truncated { offset: 8, needed: 4 }), decode(b"/a\0\0,i\0\0"));
+        assert_eq!(
+            Err(DecodeError::UnknownTypeTag { offset: 8, tag: 'x' }),
+            decode(b"/a\0\0,x\0\0")
+        );
+        assert_eq!(
+            Err(DecodeError::BadTypeTags { offset: 4, type_tags: ",]".into() }),
+            decode(b"/a\0\0,]\0\0")
+        );
+        assert_eq!(
+            Err(DecodeError::BadTypeTags { offset: 4, type_tags: ",".into() }),
+            decode(b"/a\0\0,\0\0\0\0\0\0\x01")
+        );
+        assert_eq!(
+            Err(DecodeError::Misaligned { offset: 20, size: 2 }),
+            decode(b"#bundle\0\0\0\0\0\0\0\0\x01\0\0\0\x02/a\0\0")
+        );
+        assert_eq!(
+            Err(DecodeError::EarlierTimeTag { offset: 28 }),
+            decode(b"#bundle\0\0\0\0\x02\0\0\0\0\0\0\0\x10#bundle\0\0\0\0\x01\0\0\0\0")
+        );
+    }
+
+    #[test]
+    fn test_reject_deep_nesting() {
+        let nested_arrays = |depth| {
+            let mut packet = Vec::from(&b"/a\0\0,"[..]);
+            packet.extend_from_slice("[".repeat(depth).as_bytes());
+            packet.extend_from_slice("]".repeat(depth).as_bytes());
+            packet.push(0);
+            while packet.len() & 0b11 != 0 {
+                packet.push(0);
+            }
+            packet
+        };
+        assert!(decode(&nested_arrays(MAX_NESTING)).is_ok());
+        assert_eq!(Err(DecodeError::TooDeep { offset: 4 }), decode(&nested_arrays(MAX_NESTING + 1)));
+        // would overflow the stack if decoded recursively
+        assert_eq!(Err(DecodeError::TooDeep { offset: 4 }), decode(&nested_arrays(30_000)));
+
+        let mut packet = Vec::new();
+        for _ in 0..=MAX_NESTING {
+            let mut bundle = Vec::from(&b"#bundle\0\0\0\0\0\0\0\0\x01"[..]);
+            if !packet.is_empty() {
+                bundle.extend_from_slice(&(packet.len() as i32).to_be_bytes());
+                bundle.extend_from_slice(&packet);
+            }
+            packet = bundle;
+        }
+        assert_eq!(Err(DecodeError::TooDeep { offset: 20 * MAX_NESTING }), decode(&packet));
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("/kinectosc/recenter", "/kinectosc/recenter"));
+        assert!(pattern_matches("/kinectosc/*", "/kinectosc/pause"));
+        assert!(!pattern_matches("/*", "/kinectosc/pause"));
+        assert!(pattern_matches("/kinectosc/{pause,resume}", "/kinectosc/resume"));
+        assert!(pattern_matches("/kinectosc/body/l?ck", "/kinectosc/body/lock"));
+        assert!(pattern_matches("/kinectosc/[a-p]ause", "/kinectosc/pause"));
+        assert!(!pattern_matches("/kinectosc/[!p]ause", "/kinectosc/pause"));
+        assert!(!pattern_matches("/kinectosc/pause", "/kinectosc/paused"));
+        assert!(pattern_matches("/kinectosc/*s*", "/kinectosc/resume"));
+        assert!(pattern_matches("/kinectosc/{body,filter}/*", "/kinectosc/body/unlock"));
+        assert!(pattern_matches("/kinectosc/{p,pa}use", "/kinectosc/pause"));
+        // neither the stack nor the time may blow up with many wildcards
+        let stars = format!("/{}", "*".repeat(60_000));
+        assert!(pattern_matches(&stars, "/kinectosc"));
+        assert!(!pattern_matches(&stars, "/kinectosc/pause"));
+        assert!(!pattern_matches(&format!("{}x", stars), "/kinectosc"));
+    }
 }
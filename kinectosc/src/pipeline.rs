@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nalgebra::UnitQuaternion;
+
+use super::active_sensor;
+use super::kinect::SensorReader;
+use super::metrics::{Metrics, Stage};
+
+// how long the threads block in the SDK before checking whether they should stop
+const POLL_TIMEOUT_MS: i32 = 100;
+
+/// A bounded queue that makes room for new items by dropping the oldest one.
+pub struct DropOldestQueue<T> {
+    state: Mutex<QueueState<T>>,
+    available: Condvar,
+    capacity: usize,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+impl<T> DropOldestQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            state: Mutex::new(QueueState {
+                items: VecDeque::with_capacity(capacity),
+                closed: false,
+            }),
+            available: Condvar::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the item dropped to make room, if any.
+    pub fn push(&self, item: T) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        let dropped = if state.items.len() == self.capacity {
+            state.items.pop_front()
+        } else {
+            None
+        };
+        state.items.push_back(item);
+        self.available.notify_one();
+        dropped
+    }
+
+    /// Returns `None` if nothing arrived within `timeout`, or the queue is closed and empty.
+    pub fn pop(&self, timeout: Duration) -> Option<T> {
+        let state = self.state.lock().unwrap();
+        let (mut state, _) = self
+            .available
+            .wait_timeout_while(state, timeout, |state| state.items.is_empty() && !state.closed)
+            .unwrap();
+        state.items.pop_front()
+    }
+
+    /// Wakes up every waiting `pop`. Remaining items can still be popped.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.available.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+struct Shared {
+    running: AtomicBool,
+    queue: DropOldestQueue<(k4a::Capture, Instant)>,
+    error: Mutex<Option<active_sensor::Error>>,
+    leveling: Mutex<Option<UnitQuaternion<f64>>>,
+    // device timestamp, read and enqueue time of every capture handed to the tracker,
+    // oldest first
+    in_flight: Mutex<VecDeque<(u64, Instant, Instant)>>,
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+/// Reads captures on one thread and feeds them to the tracker on another, so that
+/// captures are read while the tracker runs inference and results can be popped as
+/// soon as they are ready. Captures the tracker cannot keep up with are dropped,
+/// oldest first. The counts and timings of each stage go to `metrics`.
+pub struct Pipeline {
+    shared: Arc<Shared>,
+    capture_thread: Option<thread::JoinHandle<SensorReader>>,
+    feeder_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Pipeline {
+    pub fn start(
+        reader: SensorReader,
+        tracker: Arc<k4a::Tracker>,
+        queue_depth: usize,
+        metrics: Arc<Mutex<Metrics>>,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            running: AtomicBool::new(true),
+            queue: DropOldestQueue::new(queue_depth),
+            error: Mutex::new(None),
+            leveling: Mutex::new(None),
+            in_flight: Mutex::new(VecDeque::new()),
+            metrics,
+        });
+        let capture_thread = {
+            let shared = shared.clone();
+            thread::spawn(move || capture_loop(reader, &shared))
+        };
+        let feeder_thread = {
+            let shared = shared.clone();
+            thread::spawn(move || feed_loop(&tracker, &shared))
+        };
+        Self {
+            shared,
+            capture_thread: Some(capture_thread),
+            feeder_thread: Some(feeder_thread),
+        }
+    }
+
+    pub fn leveling(&self) -> Option<UnitQuaternion<f64>> {
+        *self.shared.leveling.lock().unwrap()
+    }
+
+    /// The error that stopped the pipeline, once every capture read before it was fed to
+    /// the tracker.
+    pub fn take_error(&self) -> Option<active_sensor::Error> {
+        if !self.feeder_thread.as_ref().is_none_or(|thread| thread.is_finished()) {
+            return None;
+        }
+        self.shared.error.lock().unwrap().take()
+    }
+
+    /// Call with the device timestamp of every result popped from the tracker. Returns
+    /// when its capture was read.
+    pub fn on_popped(&mut self, device_timestamp_usec: u64) -> Option<Instant> {
+        let now = Instant::now();
+        let mut in_flight = self.shared.in_flight.lock().unwrap();
+        let mut captured_at = None;
+        while let Some(&(timestamp, read_at, enqueued_at)) = in_flight.front() {
+            if timestamp > device_timestamp_usec {
+                break;
+            }
+            in_flight.pop_front();
+            if timestamp == device_timestamp_usec {
+                self.shared.metrics.lock().unwrap().record_stage(Stage::Inference, now - enqueued_at);
+                captured_at = Some(read_at);
+            }
+        }
+        captured_at
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::SeqCst);
+        self.shared.queue.close();
+        // the feeder holds the tracker, which has to go before the sensor
+        if let Some(thread) = self.feeder_thread.take() {
+            let _ = thread.join();
+        }
+        if let Some(thread) = self.capture_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn capture_loop(mut reader: SensorReader, shared: &Shared) -> SensorReader {
+    while shared.running.load(Ordering::SeqCst) {
+        match reader.read(POLL_TIMEOUT_MS) {
+            Ok(capture) => {
+                *shared.leveling.lock().unwrap() = reader.leveling();
+                let dropped = shared.queue.push((capture, Instant::now()));
+                shared.metrics.lock().unwrap().record_capture(dropped.is_some());
+            }
+            Err(active_sensor::Error::Timeout) => {}
+            Err(e) => {
+                *shared.error.lock().unwrap() = Some(e);
+                break;
+            }
+        }
+    }
+    shared.queue.close();
+    reader
+}
+
+fn feed_loop(tracker: &k4a::Tracker, shared: &Shared) {
+    let poll_timeout = Duration::from_millis(POLL_TIMEOUT_MS as u64);
+    loop {
+        let (capture, captured_at) = match shared.queue.pop(poll_timeout) {
+            Some(item) => item,
+            None if shared.queue.is_closed() => return,
+            None => continue,
+        };
+        let enqueue_start = Instant::now();
+        loop {
+            match tracker.enqueue_capture(capture.clone(), POLL_TIMEOUT_MS) {
+                Ok(()) => break,
+                Err(k4a::WaitError::Timeout) if shared.running.load(Ordering::SeqCst) => {}
+                Err(k4a::WaitError::Timeout) => return,
+                Err(k4a::WaitError::Failed(e)) => {
+                    *shared.error.lock().unwrap() = Some(e.into());
+                    shared.running.store(false, Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+        let enqueued_at = Instant::now();
+        if let Some(timestamp) = capture.get_device_timestamp_usec() {
+            shared.in_flight.lock().unwrap().push_back((timestamp, captured_at, enqueued_at));
+        }
+        let mut metrics = shared.metrics.lock().unwrap();
+        metrics.record_stage(Stage::Queued, enqueue_start - captured_at);
+        metrics.record_stage(Stage::Enqueue, enqueued_at - enqueue_start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest() {
+        let queue = DropOldestQueue::new(2);
+        assert_eq!(queue.push(1), None);
+        assert_eq!(queue.push(2), None);
+        assert_eq!(queue.push(3), Some(1));
+        assert_eq!(queue.pop(Duration::from_millis(0)), Some(2));
+        assert_eq!(queue.pop(Duration::from_millis(0)), Some(3));
+        assert_eq!(queue.pop(Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn test_close_wakes_up_pop() {
+        let queue = Arc::new(DropOldestQueue::<u32>::new(1));
+        let waiter = {
+            let queue = queue.clone();
+            thread::spawn(move || queue.pop(Duration::from_secs(10)))
+        };
+        thread::sleep(Duration::from_millis(10));
+        queue.close();
+        assert_eq!(waiter.join().unwrap(), None);
+        assert!(queue.is_closed());
+    }
+}
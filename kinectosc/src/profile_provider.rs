@@ -1,29 +1,36 @@
+use std::io;
 use std::path::PathBuf;
 use std::sync::mpsc;
 use std::time;
 
-use notify::{Watcher, RecursiveMode, watcher, DebouncedEvent, ReadDirectoryChangesWatcher};
+use notify::{Watcher, RecursiveMode, watcher, DebouncedEvent, RecommendedWatcher};
 
 use super::calibration;
-use nalgebra::{Translation3, UnitQuaternion};
+use nalgebra::{Isometry3, Translation3, UnitQuaternion};
 
 pub struct ProfileProvider {
     path: PathBuf,
-    watcher: ReadDirectoryChangesWatcher,
+    watcher: RecommendedWatcher,
     rx: mpsc::Receiver<DebouncedEvent>,
     pub wfd_rotation: UnitQuaternion<f64>,
     pub wfd_translation: Translation3<f64>,
 }
 
 impl ProfileProvider {
-    pub fn new(path: PathBuf) -> Self {
+    pub fn new(path: PathBuf) -> io::Result<Self> {
+        let profile = calibration::load(&path)?;
         let (tx, rx) = mpsc::channel();
-        let mut watcher = watcher(tx, time::Duration::from_secs(5)).unwrap();
-        watcher.watch(&path, RecursiveMode::NonRecursive).unwrap();
-        let profile = calibration::load(&path).unwrap();
+        let to_io_error = io::Error::other;
+        let mut watcher = watcher(tx, time::Duration::from_secs(5)).map_err(to_io_error)?;
+        watcher.watch(&path, RecursiveMode::NonRecursive).map_err(to_io_error)?;
         let wfd_rotation = profile.wfd_rotation();
         let wfd_translation = profile.wfd_translation();
-        Self { path, watcher, rx, wfd_rotation, wfd_translation }
+        Ok(Self { path, watcher, rx, wfd_rotation, wfd_translation })
+    }
+
+    /// The world from device transform, with the translation in meters.
+    pub fn calibration(&self) -> Isometry3<f64> {
+        Isometry3::from_parts(self.wfd_translation, self.wfd_rotation)
     }
 
     pub fn reload_if_updated(&mut self) {
@@ -0,0 +1,88 @@
+use std::time;
+
+use super::active_sensor;
+use super::backoff::Backoff;
+use super::skeleton_source::{BodyFrame, SkeletonSource};
+
+// how long the sensor may keep timing out before it is considered lost
+const STALL_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+type Open = Box<dyn FnMut() -> Result<Box<dyn SkeletonSource>, k4a::OpenError>>;
+
+/// Reopens the underlying source with exponential backoff whenever it fails fatally
+/// or stops delivering frames. Reports `Error::Disconnected` while it is disconnected.
+pub struct ReconnectingSource {
+    open: Open,
+    source: Option<Box<dyn SkeletonSource>>,
+    backoff: Backoff,
+    last_frame_at: time::Instant,
+}
+
+impl ReconnectingSource {
+    pub fn new(open: Open) -> Self {
+        Self {
+            open,
+            source: None,
+            backoff: Backoff::new(),
+            last_frame_at: time::Instant::now(),
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.source = None;
+        self.backoff.fail();
+    }
+
+    fn connect(&mut self, timeout: i32) -> Result<(), active_sensor::Error> {
+        if !self.backoff.is_due() {
+            let wait = self.backoff.remaining();
+            let wait = if timeout < 0 {
+                wait
+            } else {
+                wait.min(time::Duration::from_millis(timeout as u64))
+            };
+            std::thread::sleep(wait);
+            return Err(active_sensor::Error::Disconnected);
+        }
+        match (self.open)() {
+            Ok(source) => {
+                eprintln!("Sensor connected");
+                self.source = Some(source);
+                self.last_frame_at = time::Instant::now();
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("Could not open sensor, retrying in {:?}: {}", self.backoff.delay(), e);
+                self.disconnect();
+                Err(active_sensor::Error::Disconnected)
+            }
+        }
+    }
+}
+
+impl SkeletonSource for ReconnectingSource {
+    fn next_frame(&mut self, timeout: i32) -> Result<BodyFrame, active_sensor::Error> {
+        if self.source.is_none() {
+            self.connect(timeout)?;
+        }
+        let source = self.source.as_mut().unwrap();
+        match source.next_frame(timeout) {
+            Ok(frame) => {
+                self.backoff.succeed();
+                self.last_frame_at = time::Instant::now();
+                Ok(frame)
+            }
+            Err(active_sensor::Error::Timeout) if self.last_frame_at.elapsed() > STALL_TIMEOUT => {
+                eprintln!("Sensor stopped delivering frames, reconnecting");
+                self.disconnect();
+                Err(active_sensor::Error::Disconnected)
+            }
+            Err(active_sensor::Error::Fatal(e)) => {
+                eprintln!("Sensor failed, reconnecting: {}", e);
+                self.disconnect();
+                Err(active_sensor::Error::Disconnected)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
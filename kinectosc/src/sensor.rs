@@ -0,0 +1,190 @@
+//! Opens Azure Kinect devices and recordings, which needs the SDKs.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::metrics::Metrics;
+use super::skeleton_source::SkeletonSource;
+use super::{active_sensor, config, fusion, kinect, reconnect, Opt};
+
+/// Prints the warnings and errors the SDKs log to stderr.
+pub fn capture_sdk_log() {
+    if let Err(e) = k4a::set_debug_message_handler(k4a::LogLevel::K4A_LOG_LEVEL_WARNING, |message| {
+        eprintln!("{}", message);
+    }) {
+        eprintln!("Could not capture Azure Kinect SDK log: {}", e);
+    }
+}
+
+pub fn open_playback(
+    path: &Path,
+    opt: &Opt,
+    config: &config::Config,
+    metrics: Arc<Mutex<Metrics>>,
+) -> Result<Box<dyn SkeletonSource>, k4a::Error> {
+    let playback = k4a::Playback::open(&path.to_string_lossy())?;
+    let realtime_playback = active_sensor::RealtimePlayback::new(
+        playback,
+        Duration::from_secs_f64(opt.start.unwrap_or(0.0)),
+        opt.end.map(Duration::from_secs_f64),
+        opt.looping,
+    )?;
+    let kinect = kinect::Kinect::open_playback(
+        realtime_playback,
+        config.tracker.tracker_configuration(),
+        &kinect_options(&config.sensor, &config.tracker, metrics),
+    )?;
+    Ok(Box::new(kinect))
+}
+
+/// Tracks with the configured devices, and reopens them whenever they fail. Every
+/// capture is recorded to `record` if given.
+pub fn open_devices(
+    config: &config::Config,
+    record: Option<PathBuf>,
+    metrics: Arc<Mutex<Metrics>>,
+) -> Box<dyn SkeletonSource> {
+    let sensor_config = config.sensor.clone();
+    let tracker_config = config.tracker.clone();
+    let mut connections = 0;
+    if !sensor_config.devices.is_empty() {
+        let fusion_config = config.fusion.clone();
+        Box::new(reconnect::ReconnectingSource::new(Box::new(move || {
+            let devices: Vec<fusion::DeviceSetup> = sensor_config
+                .devices
+                .iter()
+                .map(|device| fusion::DeviceSetup {
+                    serial: device.serial.clone(),
+                    configuration: device.device_configuration(&sensor_config),
+                    extrinsic: device.extrinsic.isometry(),
+                    color_controls: device.color_controls(&sensor_config),
+                    record: record
+                        .as_ref()
+                        .map(|path| numbered_path(&suffixed_path(path, &device.serial), connections)),
+                })
+                .collect();
+            let kinect = fusion::MultiKinect::open(
+                &devices,
+                tracker_config.tracker_configuration(),
+                &kinect_options(&sensor_config, &tracker_config, metrics.clone()),
+                &fusion_config,
+            )?;
+            connections += 1;
+            Ok(Box::new(kinect) as Box<dyn SkeletonSource>)
+        })))
+    } else {
+        Box::new(reconnect::ReconnectingSource::new(Box::new(move || {
+            let record_path = record.as_ref().map(|path| numbered_path(path, connections));
+            let device = open_device(&sensor_config)?;
+            kinect::set_color_controls(&device, &sensor_config.color_controls);
+            let kinect = kinect::Kinect::open_device(
+                device,
+                sensor_config.device_configuration(),
+                tracker_config.tracker_configuration(),
+                &kinect_options(&sensor_config, &tracker_config, metrics.clone()),
+                record_path.as_deref(),
+            )?;
+            connections += 1;
+            Ok(Box::new(kinect) as Box<dyn SkeletonSource>)
+        })))
+    }
+}
+
+pub fn list_devices() {
+    let devices = k4a::Device::enumerate();
+    if devices.is_empty() {
+        println!("No Azure Kinect devices connected");
+    }
+    for device in devices {
+        match device {
+            Ok(device) => {
+                let version = &device.version;
+                println!(
+                    "{}: serial {}, rgb {}, depth {}, depth sensor {}, audio {}",
+                    device.index,
+                    device.serial_number,
+                    format_version(&version.rgb),
+                    format_version(&version.depth),
+                    format_version(&version.depth_sensor),
+                    format_version(&version.audio),
+                );
+            }
+            // most likely open in another process
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn open_device(sensor_config: &config::SensorConfig) -> Result<k4a::Device, k4a::OpenError> {
+    match &sensor_config.serial {
+        Some(serial) => k4a::Device::open_by_serial(serial),
+        None => Ok(k4a::Device::open(sensor_config.device_index)?),
+    }
+}
+
+/// Prints the capabilities of each control to stderr, and the current settings as JSON
+/// to stdout.
+pub fn print_color_controls(sensor_config: &config::SensorConfig) -> Result<(), k4a::OpenError> {
+    let device = open_device(sensor_config)?;
+    let mut color_controls = config::ColorControls::new();
+    for control in config::ColorControl::ALL.iter() {
+        let command = (*control).into();
+        let capabilities = device.get_color_control_capabilities(command)?;
+        let (mode, value) = device.get_color_control(command)?;
+        let setting = config::ColorControlSetting::from_k4a(mode, value);
+        eprintln!(
+            "{}: {} (range {}..={}, step {}, default {}{})",
+            serde_json::to_string(control).unwrap(),
+            serde_json::to_string(&setting).unwrap(),
+            capabilities.min_value,
+            capabilities.max_value,
+            capabilities.step_value,
+            serde_json::to_string(&config::ColorControlSetting::from_k4a(
+                capabilities.default_mode,
+                capabilities.default_value,
+            ))
+            .unwrap(),
+            if capabilities.supports_auto { ", supports auto" } else { "" },
+        );
+        color_controls.insert(*control, setting);
+    }
+    println!("{}", serde_json::to_string_pretty(&color_controls).unwrap());
+    Ok(())
+}
+
+fn format_version(version: &k4a::Version) -> String {
+    format!("{}.{}.{}", version.major, version.minor, version.iteration)
+}
+
+fn kinect_options(
+    sensor_config: &config::SensorConfig,
+    tracker_config: &config::TrackerConfig,
+    metrics: Arc<Mutex<Metrics>>,
+) -> kinect::Options {
+    kinect::Options {
+        level_with_gravity: sensor_config.level_with_gravity,
+        pipeline_depth: tracker_config.pipeline_depth(),
+        temporal_smoothing: tracker_config.temporal_smoothing,
+        metrics,
+    }
+}
+
+/// `path` itself for `n == 0`, else `path` with `-n` appended to the file stem.
+fn numbered_path(path: &Path, n: u32) -> PathBuf {
+    if n == 0 {
+        return path.to_path_buf();
+    }
+    suffixed_path(path, &n.to_string())
+}
+
+/// `path` with `-suffix` appended to the file stem.
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("-{}", suffix));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
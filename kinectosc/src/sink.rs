@@ -0,0 +1,302 @@
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nalgebra::{Isometry3, Point3, Vector3};
+use serde::Serialize;
+
+use super::filter::FilteredJoint;
+use super::joint_map::{JointMap, TrackerPose};
+use super::osc;
+use super::vmc;
+
+/// The skeletons of one frame, after filtering and body selection.
+pub struct Frame<'a> {
+    /// When the frame was captured, if it came from a sensor.
+    pub captured_at: Option<SystemTime>,
+    /// The body selected for each slot.
+    pub bodies: Vec<Option<Body<'a>>>,
+    /// Offset in millimeters to add to every position.
+    pub recenter: Vector3<f64>,
+    /// World from device transform of the calibration profile, with the translation in
+    /// meters. Applied after `recenter`.
+    pub calibration: Isometry3<f64>,
+}
+
+pub struct Body<'a> {
+    pub id: u32,
+    pub joints: &'a [FilteredJoint],
+}
+
+impl Frame<'_> {
+    /// Every mapped tracker of every slot by tracker id, with the id of the body and the
+    /// pose, or `None` for the trackers of empty slots.
+    fn trackers<'a>(&'a self, joint_map: &'a JointMap) -> impl Iterator<Item = (u32, Option<(u32, TrackerPose)>)> + 'a {
+        self.bodies.iter().enumerate().flat_map(move |(slot, body)| {
+            let base_id = slot as u32 * joint_map.id_stride();
+            joint_map.trackers.iter().map(move |tracker| {
+                let pose = body
+                    .as_ref()
+                    .map(|body| (body.id, tracker.pose(&body.joints[tracker.joint.index()])));
+                (base_id + tracker.id, pose)
+            })
+        })
+    }
+
+    /// `position` in millimeters, recentered and calibrated.
+    fn calibrate_position(&self, position: &Point3<f64>) -> Point3<f64> {
+        self.calibration.rotation * (position + self.recenter) + self.calibration.translation.vector * 1000.0
+    }
+
+    fn calibrate_pose(&self, pose: &TrackerPose) -> TrackerPose {
+        TrackerPose {
+            position: self.calibrate_position(&pose.position),
+            orientation: self.calibration.rotation * pose.orientation,
+            velocity: self.calibration.rotation * pose.velocity,
+            is_valid: pose.is_valid,
+        }
+    }
+
+    fn calibrate_joint(&self, joint: &FilteredJoint) -> FilteredJoint {
+        let rotation = self.calibration.rotation;
+        FilteredJoint {
+            raw_position: self.calibrate_position(&joint.raw_position),
+            filtered_position: self.calibrate_position(&joint.filtered_position),
+            trend: rotation * joint.trend,
+            predicted_position: self.calibrate_position(&joint.predicted_position),
+            raw_orientation: rotation * joint.raw_orientation,
+            filtered_orientation: rotation * joint.filtered_orientation,
+            orientation_trend: rotation.to_rotation_matrix() * joint.orientation_trend * rotation.inverse().to_rotation_matrix(),
+            predicted_orientation: rotation * joint.predicted_orientation,
+            frame_count: joint.frame_count,
+        }
+    }
+
+    fn timetag(&self) -> osc::TimeTag {
+        match self.captured_at {
+            Some(captured_at) => osc::TimeTag::from_system_time(captured_at),
+            None => osc::TimeTag::IMMEDIATELY,
+        }
+    }
+}
+
+/// Receives every processed frame, unless output is paused.
+pub trait OutputSink {
+    fn send(&mut self, frame: &Frame) -> io::Result<()>;
+}
+
+/// Sends `/Tracker/Pose` for every mapped tracker, or `/Tracker/NoBody` while its slot
+/// is empty. The calibration is sent along for the receiver to apply.
+pub struct TrackerSink {
+    client: osc::Client,
+    joint_map: JointMap,
+    /// Present when the messages of a frame are bundled.
+    bundle: Option<osc::Bundle>,
+}
+
+impl TrackerSink {
+    pub fn new(client: osc::Client, joint_map: JointMap, bundle: bool) -> Self {
+        TrackerSink {
+            client,
+            joint_map,
+            bundle: if bundle { Some(osc::Bundle::new(osc::TimeTag::IMMEDIATELY)) } else { None },
+        }
+    }
+
+    /// Sends `message` right away, or adds it to the bundle to be sent with the rest of
+    /// the frame.
+    fn send_or_bundle<M: osc::Message>(&mut self, message: M) -> io::Result<()> {
+        match &mut self.bundle {
+            Some(bundle) => bundle.push(message),
+            None => self.client.send(message),
+        }
+    }
+}
+
+impl OutputSink for TrackerSink {
+    /// Sends every message even after one fails, and returns the first error.
+    fn send(&mut self, frame: &Frame) -> io::Result<()> {
+        if let Some(bundle) = &mut self.bundle {
+            bundle.clear(frame.timetag());
+        }
+        let trackers: Vec<_> = frame.trackers(&self.joint_map).collect();
+        let mut result = Ok(());
+        for (id, pose) in trackers {
+            let sent = match pose {
+                Some((_, pose)) => self.send_or_bundle(build_pose_message(&frame.calibration, &pose, &frame.recenter, id)),
+                None => self.send_or_bundle(osc::NoBodyMessage { id }),
+            };
+            result = result.and(sent);
+        }
+        if let Some(bundle) = &self.bundle {
+            if !bundle.is_empty() {
+                result = result.and(self.client.send_bundle(bundle));
+            }
+        }
+        result
+    }
+}
+
+fn build_pose_message(
+    calibration: &Isometry3<f64>,
+    pose: &TrackerPose,
+    recenter: &Vector3<f64>,
+    id: u32,
+) -> osc::PoseMessage {
+    osc::PoseMessage {
+        id,
+        is_valid: pose.is_valid,
+        wfd_rotation: calibration.rotation,
+        wfd_translation: calibration.translation,
+        position: (pose.position + recenter) / 1000.0,
+        orientation: pose.orientation,
+        velocity: pose.velocity / 1000.0 * 30.0,
+    }
+}
+
+/// Drives a Virtual Motion Capture avatar after the body of the first slot, and sends
+/// its mapped trackers along, both calibrated.
+pub struct VmcSink {
+    client: osc::Client,
+    joint_map: JointMap,
+    encoder: vmc::Encoder,
+    bundle: osc::Bundle,
+}
+
+impl VmcSink {
+    pub fn new(client: osc::Client, joint_map: JointMap) -> Self {
+        VmcSink {
+            client,
+            joint_map,
+            encoder: vmc::Encoder::new(),
+            bundle: osc::Bundle::new(osc::TimeTag::IMMEDIATELY),
+        }
+    }
+}
+
+impl OutputSink for VmcSink {
+    fn send(&mut self, frame: &Frame) -> io::Result<()> {
+        self.bundle.clear(frame.timetag());
+        let joints: Option<Vec<_>> = frame
+            .bodies
+            .first()
+            .and_then(|body| body.as_ref())
+            .map(|body| body.joints.iter().map(|joint| frame.calibrate_joint(joint)).collect());
+        let trackers: Vec<_> = match &joints {
+            Some(joints) => self
+                .joint_map
+                .trackers
+                .iter()
+                .map(|tracker| (tracker.id, tracker.pose(&joints[tracker.joint.index()])))
+                .collect(),
+            None => Vec::new(),
+        };
+        // already recentered by the calibration
+        self.encoder
+            .encode(&mut self.bundle, joints.as_deref(), &trackers, &Vector3::zeros())?;
+        self.client.send_bundle(&self.bundle)
+    }
+}
+
+#[derive(Serialize)]
+struct FrameRecord {
+    /// Seconds since the Unix epoch.
+    captured_at: Option<f64>,
+    trackers: Vec<TrackerRecord>,
+}
+
+#[derive(Serialize)]
+struct TrackerRecord {
+    id: u32,
+    body: u32,
+    valid: bool,
+    /// Meters, calibrated.
+    position: [f64; 3],
+    /// w, x, y, z.
+    orientation: [f64; 4],
+}
+
+/// Appends the calibrated poses of the trackers of the selected bodies to a file, one
+/// JSON object per frame and line.
+pub struct FileSink {
+    writer: BufWriter<fs::File>,
+    joint_map: JointMap,
+}
+
+impl FileSink {
+    pub fn create<P: AsRef<Path>>(path: P, joint_map: JointMap) -> io::Result<Self> {
+        let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink {
+            writer: BufWriter::new(file),
+            joint_map,
+        })
+    }
+}
+
+impl OutputSink for FileSink {
+    fn send(&mut self, frame: &Frame) -> io::Result<()> {
+        let record = FrameRecord {
+            captured_at: frame
+                .captured_at
+                .and_then(|captured_at| captured_at.duration_since(UNIX_EPOCH).ok())
+                .map(|since_epoch| since_epoch.as_secs_f64()),
+            trackers: frame
+                .trackers(&self.joint_map)
+                .filter_map(|(id, pose)| pose.map(|(body, pose)| (id, body, pose)))
+                .map(|(id, body, pose)| {
+                    let pose = frame.calibrate_pose(&pose);
+                    let position = pose.position / 1000.0;
+                    let q = pose.orientation.quaternion();
+                    TrackerRecord {
+                        id,
+                        body,
+                        valid: pose.is_valid,
+                        position: [position.x, position.y, position.z],
+                        orientation: [q.w, q.i, q.j, q.k],
+                    }
+                })
+                .collect(),
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        // a line at a time, so the file can be followed while tracking
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_sink() {
+        let path = std::env::temp_dir().join(format!("kinectosc-sink-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let joints = vec![FilteredJoint::default(); k4a::joint_id::K4ABT_JOINT_COUNT as usize];
+        let mut sink = FileSink::create(&path, JointMap::default()).unwrap();
+        let frame = Frame {
+            captured_at: None,
+            bodies: vec![Some(Body { id: 7, joints: &joints }), None],
+            recenter: Vector3::new(1000.0, 0.0, 0.0),
+            calibration: Isometry3::identity(),
+        };
+        sink.send(&frame).unwrap();
+        let calibration = Isometry3::new(Vector3::new(0.0, 0.5, 0.0), Vector3::z() * std::f64::consts::FRAC_PI_2);
+        sink.send(&Frame { calibration, ..frame }).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(2, lines.len());
+        let trackers = lines[0]["trackers"].as_array().unwrap();
+        // only the first slot has a body
+        assert_eq!(JointMap::default().trackers.len(), trackers.len());
+        assert_eq!(7, trackers[0]["body"]);
+        assert_eq!(1.0, trackers[0]["position"][0]);
+        // recentered, turned a quarter about z, then moved up
+        let position = &lines[1]["trackers"][0]["position"];
+        assert!(position[0].as_f64().unwrap().abs() < 1e-9);
+        assert!((position[1].as_f64().unwrap() - 1.5).abs() < 1e-9);
+    }
+}
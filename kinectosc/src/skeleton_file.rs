@@ -0,0 +1,284 @@
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path;
+use std::time;
+
+use super::active_sensor;
+use super::skeleton_source::{BodyFrame, SkeletonSource};
+
+// File layout (all integers and floats are little endian):
+//
+//   header: magic "KOSK", version: u16, joint count: u16
+//   frame:  device timestamp (usec): u64, body count: u32, body*
+//   body:   id: u32, joint*
+//   joint:  position x, y, z: f32, orientation w, x, y, z: f32, confidence level: u8
+const MAGIC: &[u8; 4] = b"KOSK";
+const VERSION: u16 = 1;
+const JOINT_COUNT: usize = k4a::joint_id::K4ABT_JOINT_COUNT as usize;
+// the body index map tells no more bodies apart
+const MAX_BODIES: u32 = k4a::BODY_INDEX_MAP_BACKGROUND as u32;
+
+pub struct SkeletonWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> SkeletonWriter<W> {
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(MAGIC)?;
+        inner.write_all(&VERSION.to_le_bytes())?;
+        inner.write_all(&(JOINT_COUNT as u16).to_le_bytes())?;
+        Ok(Self { inner })
+    }
+
+    pub fn write_frame(&mut self, frame: &BodyFrame) -> io::Result<()> {
+        let w = &mut self.inner;
+        w.write_all(&frame.device_timestamp_usec.to_le_bytes())?;
+        w.write_all(&(frame.bodies.len() as u32).to_le_bytes())?;
+        for body in &frame.bodies {
+            w.write_all(&body.id.to_le_bytes())?;
+            for joint in body.skeleton.joints.iter() {
+                let p = &joint.position;
+                let q = &joint.orientation;
+                for f in &[p.x, p.y, p.z, q.w, q.x, q.y, q.z] {
+                    w.write_all(&f.to_le_bytes())?;
+                }
+                w.write_all(&[joint.confidence_level.0 as u8])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct SkeletonReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> SkeletonReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a skeleton file"));
+        }
+        let version = read_u16(&mut inner)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("unsupported skeleton file version {}", version)));
+        }
+        let joint_count = read_u16(&mut inner)?;
+        if joint_count as usize != JOINT_COUNT {
+            return Err(invalid_data(&format!("unsupported joint count {}", joint_count)));
+        }
+        Ok(Self { inner })
+    }
+
+    /// Returns `Ok(None)` at the end of the file.
+    pub fn read_frame(&mut self) -> io::Result<Option<BodyFrame>> {
+        let mut timestamp = [0u8; 8];
+        match self.inner.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let r = &mut self.inner;
+        let num_bodies = read_u32(r)?;
+        if num_bodies > MAX_BODIES {
+            return Err(invalid_data(&format!("invalid body count {}", num_bodies)));
+        }
+        let mut bodies = Vec::new();
+        for _ in 0..num_bodies {
+            let id = read_u32(r)?;
+            let mut joints = Vec::with_capacity(JOINT_COUNT);
+            for _ in 0..JOINT_COUNT {
+                let mut f = [0f32; 7];
+                for v in f.iter_mut() {
+                    *v = read_f32(r)?;
+                }
+                let mut confidence_level = [0u8; 1];
+                r.read_exact(&mut confidence_level)?;
+                joints.push(k4a::Joint {
+                    position: k4a::Float3 { x: f[0], y: f[1], z: f[2] },
+                    orientation: k4a::Quaternion { w: f[3], x: f[4], y: f[5], z: f[6] },
+                    confidence_level: k4a::JointConfidenceLevel(confidence_level[0] as _),
+                });
+            }
+            let mut joints = joints.into_iter();
+            bodies.push(k4a::Body {
+                id,
+                skeleton: k4a::Skeleton {
+                    joints: std::array::from_fn(|_| joints.next().unwrap()),
+                },
+            });
+        }
+        Ok(Some(BodyFrame {
+            device_timestamp_usec: u64::from_le_bytes(timestamp),
+            captured_at: time::Instant::now(),
+            bodies,
+        }))
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut buf = [0u8; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+pub struct RecordingSource {
+    source: Box<dyn SkeletonSource>,
+    writer: Option<SkeletonWriter<BufWriter<fs::File>>>,
+}
+
+impl RecordingSource {
+    pub fn create<P: AsRef<path::Path>>(source: Box<dyn SkeletonSource>, path: P) -> io::Result<Self> {
+        let file = fs::File::create(path)?;
+        let writer = SkeletonWriter::new(BufWriter::new(file))?;
+        Ok(Self {
+            source,
+            writer: Some(writer),
+        })
+    }
+}
+
+impl SkeletonSource for RecordingSource {
+    fn next_frame(&mut self, timeout: i32) -> Result<BodyFrame, active_sensor::Error> {
+        let frame = self.source.next_frame(timeout)?;
+        if let Some(writer) = &mut self.writer {
+            if let Err(e) = writer.write_frame(&frame) {
+                eprintln!("Could not record skeleton frame, recording stopped: {}", e);
+                self.writer = None;
+            }
+        }
+        Ok(frame)
+    }
+}
+
+impl Drop for RecordingSource {
+    fn drop(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(e) = writer.flush() {
+                eprintln!("Could not flush skeleton recording: {}", e);
+            }
+        }
+    }
+}
+
+pub struct ReplaySource {
+    reader: SkeletonReader<BufReader<fs::File>>,
+    start: Option<(time::Instant, u64)>,
+}
+
+impl ReplaySource {
+    pub fn open<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let reader = SkeletonReader::new(BufReader::new(file))?;
+        Ok(Self { reader, start: None })
+    }
+}
+
+impl SkeletonSource for ReplaySource {
+    fn next_frame(&mut self, _timeout: i32) -> Result<BodyFrame, active_sensor::Error> {
+        let frame = match self.reader.read_frame()? {
+            Some(frame) => frame,
+            None => return Err(active_sensor::Error::End),
+        };
+        let (start_time, start_timestamp) = *self
+            .start
+            .get_or_insert((time::Instant::now(), frame.device_timestamp_usec));
+        let offset = frame.device_timestamp_usec.saturating_sub(start_timestamp);
+        let due = start_time + time::Duration::from_micros(offset);
+        let now = time::Instant::now();
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeleton_source::test_body;
+
+    fn body(id: u32, offset: f32) -> k4a::Body {
+        test_body(id, |idx| k4a::Joint {
+            position: k4a::Float3 { x: idx as f32, y: offset, z: -offset },
+            orientation: k4a::Quaternion { w: 0.5, x: -0.5, y: 0.5, z: -0.5 },
+            confidence_level: k4a::JointConfidenceLevel((idx % 4) as _),
+        })
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let frames = vec![
+            BodyFrame { device_timestamp_usec: 33_333, captured_at: time::Instant::now(), bodies: vec![] },
+            BodyFrame {
+                device_timestamp_usec: 66_666,
+                captured_at: time::Instant::now(),
+                bodies: vec![body(1, 1.5), body(7, -2.25)],
+            },
+        ];
+        let mut buf = Vec::new();
+        let mut writer = SkeletonWriter::new(&mut buf).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+
+        let mut reader = SkeletonReader::new(&buf[..]).unwrap();
+        for expected in &frames {
+            let actual = reader.read_frame().unwrap().unwrap();
+            assert_eq!(expected.device_timestamp_usec, actual.device_timestamp_usec);
+            assert_eq!(expected.bodies.len(), actual.bodies.len());
+            for (e, a) in expected.bodies.iter().zip(actual.bodies.iter()) {
+                assert_eq!(e.id, a.id);
+                for (ej, aj) in e.skeleton.joints.iter().zip(a.skeleton.joints.iter()) {
+                    assert_eq!(ej.position.x, aj.position.x);
+                    assert_eq!(ej.position.y, aj.position.y);
+                    assert_eq!(ej.position.z, aj.position.z);
+                    assert_eq!(ej.orientation.w, aj.orientation.w);
+                    assert_eq!(ej.orientation.z, aj.orientation.z);
+                    assert_eq!(ej.confidence_level, aj.confidence_level);
+                }
+            }
+        }
+        assert!(reader.read_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reject_unknown_file() {
+        let buf = b"RIFF\x01\x00\x20\x00";
+        assert!(SkeletonReader::new(&buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_reject_bogus_body_count() {
+        let mut buf = Vec::new();
+        SkeletonWriter::new(&mut buf).unwrap();
+        buf.extend_from_slice(&33_333u64.to_le_bytes());
+        buf.extend_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = SkeletonReader::new(&buf[..]).unwrap();
+        match reader.read_frame() {
+            Err(e) => assert_eq!(io::ErrorKind::InvalidData, e.kind()),
+            Ok(_) => panic!("read a frame with {} bodies", u32::MAX),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Instant;
+
+use super::active_sensor;
+
+pub struct BodyFrame {
+    pub device_timestamp_usec: u64,
+    /// When the capture the bodies were tracked in was read from the sensor.
+    pub captured_at: Instant,
+    pub bodies: Vec<k4a::Body>,
+}
+
+impl BodyFrame {
+    #[cfg(feature = "sensor")]
+    pub fn from_frame(frame: &k4a::Frame, captured_at: Instant) -> Result<Self, k4a::Error> {
+        let num_bodies = frame.get_num_bodies();
+        let mut bodies = Vec::with_capacity(num_bodies as usize);
+        for index in 0..num_bodies {
+            bodies.push(k4a::Body {
+                id: frame.get_body_id(index),
+                skeleton: frame.get_body_skeleton(index)?,
+            });
+        }
+        Ok(Self {
+            device_timestamp_usec: frame.get_device_timestamp_usec(),
+            captured_at,
+            bodies,
+        })
+    }
+}
+
+/// A joint at `x`, `y`, `z` in millimeters, unrotated and tracked with medium confidence.
+pub fn joint_at(x: f32, y: f32, z: f32) -> k4a::Joint {
+    k4a::Joint {
+        position: k4a::Float3 { x, y, z },
+        orientation: k4a::Quaternion { w: 1., x: 0., y: 0., z: 0. },
+        confidence_level: k4a::JointConfidenceLevel::K4ABT_JOINT_CONFIDENCE_MEDIUM,
+    }
+}
+
+/// A body with the joint of every index made by `joint`.
+#[cfg(test)]
+pub fn test_body(id: u32, joint: impl FnMut(usize) -> k4a::Joint) -> k4a::Body {
+    k4a::Body {
+        id,
+        skeleton: k4a::Skeleton {
+            joints: std::array::from_fn(joint),
+        },
+    }
+}
+
+pub trait SkeletonSource {
+    fn next_frame(&mut self, timeout: i32) -> Result<BodyFrame, active_sensor::Error>;
+}
@@ -0,0 +1,320 @@
+use std::f64::consts::PI;
+use std::time;
+
+use nalgebra::{Point3, Rotation3, Vector3};
+use k4a::joint_id;
+
+use super::active_sensor;
+use super::skeleton_source::{joint_at, BodyFrame, SkeletonSource};
+
+const JOINT_COUNT: usize = joint_id::K4ABT_JOINT_COUNT as usize;
+
+// Rest pose of a person facing the sensor, relative to the pelvis, in depth camera
+// coordinates (millimeters, x: sensor's right = person's left, y: down, z: away from the sensor).
+const REST_POSE: [(joint_id::Type, [f64; 3]); JOINT_COUNT] = [
+    (joint_id::K4ABT_JOINT_PELVIS, [0., 0., 0.]),
+    (joint_id::K4ABT_JOINT_SPINE_NAVEL, [0., -200., 0.]),
+    (joint_id::K4ABT_JOINT_SPINE_CHEST, [0., -380., 0.]),
+    (joint_id::K4ABT_JOINT_NECK, [0., -560., 0.]),
+    (joint_id::K4ABT_JOINT_CLAVICLE_LEFT, [40., -520., 0.]),
+    (joint_id::K4ABT_JOINT_SHOULDER_LEFT, [180., -520., 0.]),
+    (joint_id::K4ABT_JOINT_ELBOW_LEFT, [180., -250., 0.]),
+    (joint_id::K4ABT_JOINT_WRIST_LEFT, [180., -10., 0.]),
+    (joint_id::K4ABT_JOINT_HAND_LEFT, [180., 60., 0.]),
+    (joint_id::K4ABT_JOINT_HANDTIP_LEFT, [180., 140., 0.]),
+    (joint_id::K4ABT_JOINT_THUMB_LEFT, [150., 80., -30.]),
+    (joint_id::K4ABT_JOINT_CLAVICLE_RIGHT, [-40., -520., 0.]),
+    (joint_id::K4ABT_JOINT_SHOULDER_RIGHT, [-180., -520., 0.]),
+    (joint_id::K4ABT_JOINT_ELBOW_RIGHT, [-180., -250., 0.]),
+    (joint_id::K4ABT_JOINT_WRIST_RIGHT, [-180., -10., 0.]),
+    (joint_id::K4ABT_JOINT_HAND_RIGHT, [-180., 60., 0.]),
+    (joint_id::K4ABT_JOINT_HANDTIP_RIGHT, [-180., 140., 0.]),
+    (joint_id::K4ABT_JOINT_THUMB_RIGHT, [-150., 80., -30.]),
+    (joint_id::K4ABT_JOINT_HIP_LEFT, [90., 0., 0.]),
+    (joint_id::K4ABT_JOINT_KNEE_LEFT, [90., 430., 0.]),
+    (joint_id::K4ABT_JOINT_ANKLE_LEFT, [90., 850., 0.]),
+    (joint_id::K4ABT_JOINT_FOOT_LEFT, [90., 920., -120.]),
+    (joint_id::K4ABT_JOINT_HIP_RIGHT, [-90., 0., 0.]),
+    (joint_id::K4ABT_JOINT_KNEE_RIGHT, [-90., 430., 0.]),
+    (joint_id::K4ABT_JOINT_ANKLE_RIGHT, [-90., 850., 0.]),
+    (joint_id::K4ABT_JOINT_FOOT_RIGHT, [-90., 920., -120.]),
+    (joint_id::K4ABT_JOINT_HEAD, [0., -680., 0.]),
+    (joint_id::K4ABT_JOINT_NOSE, [0., -650., -90.]),
+    (joint_id::K4ABT_JOINT_EYE_LEFT, [30., -690., -80.]),
+    (joint_id::K4ABT_JOINT_EAR_LEFT, [70., -680., 0.]),
+    (joint_id::K4ABT_JOINT_EYE_RIGHT, [-30., -690., -80.]),
+    (joint_id::K4ABT_JOINT_EAR_RIGHT, [-70., -680., 0.]),
+];
+
+const LEFT_ARM: [joint_id::Type; 5] = [
+    joint_id::K4ABT_JOINT_ELBOW_LEFT,
+    joint_id::K4ABT_JOINT_WRIST_LEFT,
+    joint_id::K4ABT_JOINT_HAND_LEFT,
+    joint_id::K4ABT_JOINT_HANDTIP_LEFT,
+    joint_id::K4ABT_JOINT_THUMB_LEFT,
+];
+
+const RIGHT_ARM: [joint_id::Type; 5] = [
+    joint_id::K4ABT_JOINT_ELBOW_RIGHT,
+    joint_id::K4ABT_JOINT_WRIST_RIGHT,
+    joint_id::K4ABT_JOINT_HAND_RIGHT,
+    joint_id::K4ABT_JOINT_HANDTIP_RIGHT,
+    joint_id::K4ABT_JOINT_THUMB_RIGHT,
+];
+
+const LEFT_LEG: [joint_id::Type; 3] = [
+    joint_id::K4ABT_JOINT_KNEE_LEFT,
+    joint_id::K4ABT_JOINT_ANKLE_LEFT,
+    joint_id::K4ABT_JOINT_FOOT_LEFT,
+];
+
+const RIGHT_LEG: [joint_id::Type; 3] = [
+    joint_id::K4ABT_JOINT_KNEE_RIGHT,
+    joint_id::K4ABT_JOINT_ANKLE_RIGHT,
+    joint_id::K4ABT_JOINT_FOOT_RIGHT,
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Motion {
+    Absent,
+    Standing,
+    Walking,
+    ArmRaise,
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub motion: Motion,
+    pub duration: time::Duration,
+}
+
+impl Step {
+    pub fn new(motion: Motion, secs: f64) -> Self {
+        Self {
+            motion,
+            duration: time::Duration::from_secs_f64(secs),
+        }
+    }
+}
+
+pub fn default_script() -> Vec<Step> {
+    vec![
+        Step::new(Motion::Absent, 2.),
+        Step::new(Motion::Standing, 3.),
+        Step::new(Motion::Walking, 6.),
+        Step::new(Motion::ArmRaise, 4.),
+        Step::new(Motion::Standing, 2.),
+        Step::new(Motion::Absent, 2.),
+    ]
+}
+
+pub struct SyntheticSource {
+    script: Vec<Step>,
+    origin: Point3<f64>,
+    interval: time::Duration,
+    frame_count: u64,
+    body_id: u32,
+    was_present: bool,
+    last_time: time::Instant,
+}
+
+impl SyntheticSource {
+    pub fn new(script: Vec<Step>, fps: u32) -> Self {
+        assert!(!script.is_empty(), "script must have at least one step");
+        Self {
+            script,
+            origin: Point3::new(0., 0., 2500.),
+            interval: time::Duration::from_secs(1) / fps,
+            frame_count: 0,
+            body_id: 0,
+            was_present: false,
+            last_time: time::Instant::now(),
+        }
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.interval.as_secs_f64() * self.frame_count as f64
+    }
+
+    fn current_step(&self) -> (&Step, f64) {
+        let total: f64 = self.script.iter().map(|step| step.duration.as_secs_f64()).sum();
+        let mut t = if total > 0. { self.elapsed() % total } else { 0. };
+        for step in &self.script {
+            let duration = step.duration.as_secs_f64();
+            if t < duration {
+                return (step, t);
+            }
+            t -= duration;
+        }
+        (&self.script[self.script.len() - 1], t)
+    }
+
+    fn pose(&self, motion: Motion, t: f64) -> [Point3<f64>; JOINT_COUNT] {
+        let mut pose = [Point3::origin(); JOINT_COUNT];
+        for (id, [x, y, z]) in REST_POSE.iter() {
+            pose[*id as usize] = Point3::new(*x, *y, *z);
+        }
+        // breathing-like sway so that nothing is perfectly still
+        let sway = Vector3::new(10. * (t * 0.7 * PI).sin(), 0., 0.);
+        let mut root = self.origin + sway;
+        match motion {
+            Motion::Absent | Motion::Standing => {}
+            Motion::Walking => {
+                // cross the field of view from side to side, two steps per second
+                root.x += 800. * (t * PI / 3.).sin();
+                let phase = t * 2. * PI;
+                swing(&mut pose, joint_id::K4ABT_JOINT_HIP_LEFT, &LEFT_LEG, 0.35 * phase.sin());
+                swing(&mut pose, joint_id::K4ABT_JOINT_HIP_RIGHT, &RIGHT_LEG, -0.35 * phase.sin());
+                swing(&mut pose, joint_id::K4ABT_JOINT_SHOULDER_LEFT, &LEFT_ARM, -0.3 * phase.sin());
+                swing(&mut pose, joint_id::K4ABT_JOINT_SHOULDER_RIGHT, &RIGHT_ARM, 0.3 * phase.sin());
+            }
+            Motion::ArmRaise => {
+                // lateral raise up to overhead and back down
+                let angle = PI * (1. - (t * PI / 2.).cos()) / 2.;
+                raise(&mut pose, joint_id::K4ABT_JOINT_SHOULDER_LEFT, &LEFT_ARM, -angle);
+                raise(&mut pose, joint_id::K4ABT_JOINT_SHOULDER_RIGHT, &RIGHT_ARM, angle);
+            }
+        }
+        for point in pose.iter_mut() {
+            *point += root.coords;
+        }
+        pose
+    }
+}
+
+fn swing(pose: &mut [Point3<f64>; JOINT_COUNT], pivot: joint_id::Type, chain: &[joint_id::Type], angle: f64) {
+    rotate_chain(pose, pivot, chain, Rotation3::from_axis_angle(&Vector3::x_axis(), angle));
+}
+
+fn raise(pose: &mut [Point3<f64>; JOINT_COUNT], pivot: joint_id::Type, chain: &[joint_id::Type], angle: f64) {
+    rotate_chain(pose, pivot, chain, Rotation3::from_axis_angle(&Vector3::z_axis(), angle));
+}
+
+fn rotate_chain(
+    pose: &mut [Point3<f64>; JOINT_COUNT],
+    pivot: joint_id::Type,
+    chain: &[joint_id::Type],
+    rotation: Rotation3<f64>,
+) {
+    let pivot = pose[pivot as usize];
+    for id in chain {
+        let joint = &mut pose[*id as usize];
+        *joint = pivot + rotation * (*joint - pivot);
+    }
+}
+
+fn skeleton_from_pose(pose: &[Point3<f64>; JOINT_COUNT]) -> k4a::Skeleton {
+    k4a::Skeleton {
+        joints: std::array::from_fn(|idx| joint_at(pose[idx].x as f32, pose[idx].y as f32, pose[idx].z as f32)),
+    }
+}
+
+impl SkeletonSource for SyntheticSource {
+    fn next_frame(&mut self, _timeout: i32) -> Result<BodyFrame, active_sensor::Error> {
+        let elapsed = self.last_time.elapsed();
+        if self.interval > elapsed {
+            std::thread::sleep(self.interval - elapsed);
+        }
+        self.last_time = time::Instant::now();
+
+        let (step, t) = self.current_step();
+        let motion = step.motion;
+        let mut bodies = Vec::new();
+        if motion != Motion::Absent {
+            if !self.was_present {
+                // the tracker hands out a new id whenever a body re-enters
+                self.body_id += 1;
+            }
+            bodies.push(k4a::Body {
+                id: self.body_id,
+                skeleton: skeleton_from_pose(&self.pose(motion, t)),
+            });
+        }
+        self.was_present = motion != Motion::Absent;
+
+        let device_timestamp_usec = (self.elapsed() * 1_000_000.) as u64;
+        self.frame_count += 1;
+        Ok(BodyFrame {
+            device_timestamp_usec,
+            captured_at: time::Instant::now(),
+            bodies,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relative_to_pelvis(pose: &[Point3<f64>; JOINT_COUNT], joint: joint_id::Type) -> Vector3<f64> {
+        pose[joint as usize] - pose[joint_id::K4ABT_JOINT_PELVIS as usize]
+    }
+
+    #[test]
+    fn test_rest_pose() {
+        let source = SyntheticSource::new(default_script(), 30);
+        let pose = source.pose(Motion::Standing, 0.);
+        assert_eq!(Point3::new(0., 0., 2500.), pose[joint_id::K4ABT_JOINT_PELVIS as usize]);
+        // y points down
+        assert!(relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_HEAD).y < -600.);
+        assert!(relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_FOOT_LEFT).y > 900.);
+        assert!(relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_SHOULDER_LEFT).x > 0.);
+        assert!(relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_SHOULDER_RIGHT).x < 0.);
+    }
+
+    #[test]
+    fn test_arm_raise() {
+        let source = SyntheticSource::new(default_script(), 30);
+        // halfway through, both arms point straight up
+        let pose = source.pose(Motion::ArmRaise, 2.);
+        let left = relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_WRIST_LEFT);
+        assert!((left - Vector3::new(180., -1030., 0.)).norm() < 1e-9, "{}", left);
+        let right = relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_WRIST_RIGHT);
+        assert!((right - Vector3::new(-180., -1030., 0.)).norm() < 1e-9, "{}", right);
+    }
+
+    #[test]
+    fn test_walking_keeps_bone_lengths() {
+        let source = SyntheticSource::new(default_script(), 30);
+        let rest = source.pose(Motion::Standing, 0.);
+        let bones = [
+            (joint_id::K4ABT_JOINT_HIP_LEFT, joint_id::K4ABT_JOINT_KNEE_LEFT),
+            (joint_id::K4ABT_JOINT_KNEE_RIGHT, joint_id::K4ABT_JOINT_ANKLE_RIGHT),
+            (joint_id::K4ABT_JOINT_SHOULDER_LEFT, joint_id::K4ABT_JOINT_ELBOW_LEFT),
+            (joint_id::K4ABT_JOINT_ELBOW_RIGHT, joint_id::K4ABT_JOINT_WRIST_RIGHT),
+        ];
+        for frame in 0..30 {
+            let pose = source.pose(Motion::Walking, frame as f64 / 10.);
+            for (a, b) in bones.iter() {
+                let length = (pose[*a as usize] - pose[*b as usize]).norm();
+                let rest_length = (rest[*a as usize] - rest[*b as usize]).norm();
+                assert!((length - rest_length).abs() < 1e-9);
+            }
+        }
+        // the legs swing in opposite directions
+        let pose = source.pose(Motion::Walking, 0.25);
+        assert!(relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_ANKLE_LEFT).z > 100.);
+        assert!(relative_to_pelvis(&pose, joint_id::K4ABT_JOINT_ANKLE_RIGHT).z < -100.);
+    }
+
+    #[test]
+    fn test_script() {
+        let script = vec![
+            Step::new(Motion::Absent, 0.0015),
+            Step::new(Motion::Standing, 0.003),
+            Step::new(Motion::Absent, 0.002),
+            Step::new(Motion::Standing, 0.002),
+        ];
+        let mut source = SyntheticSource::new(script, 1000);
+        let frames: Vec<BodyFrame> = (0..10).map(|_| source.next_frame(0).unwrap()).collect();
+        let ids: Vec<Option<u32>> = frames.iter().map(|frame| frame.bodies.first().map(|body| body.id)).collect();
+        // a new id each time the body re-enters, and the script starts over at the end
+        assert_eq!(
+            vec![None, None, Some(1), Some(1), Some(1), None, None, Some(2), Some(2), None],
+            ids
+        );
+        for (n, frame) in frames.iter().enumerate() {
+            assert_eq!(n as u64 * 1000, frame.device_timestamp_usec);
+        }
+    }
+}
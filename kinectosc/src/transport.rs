@@ -0,0 +1,217 @@
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::backoff::Backoff;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
+// a receiver that stalls longer than this is disconnected, not waited for
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+const SLIP_END: u8 = 0xc0;
+const SLIP_ESC: u8 = 0xdb;
+const SLIP_ESC_END: u8 = 0xdc;
+const SLIP_ESC_ESC: u8 = 0xdd;
+
+#[derive(Debug, Clone)]
+pub enum Destination {
+    Udp(SocketAddr),
+    Tcp(SocketAddr, Framing),
+    /// A Unix datagram socket.
+    Unix(PathBuf),
+}
+
+/// How packets are delimited on a stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Framing {
+    /// Double-ended SLIP, as in OSC 1.1.
+    Slip,
+    /// A big-endian 32 bit size before each packet, as in OSC 1.0.
+    LengthPrefix,
+}
+
+/// Delivers encoded packets to one destination.
+pub trait Transport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()>;
+}
+
+/// Opens a transport for `destination`. UDP destinations send from `socket`, so that
+/// receivers can reply to its address.
+pub fn open(destination: &Destination, socket: &UdpSocket) -> io::Result<Box<dyn Transport>> {
+    Ok(match destination {
+        Destination::Udp(target) => Box::new(UdpTransport {
+            socket: socket.try_clone()?,
+            target: *target,
+        }),
+        Destination::Tcp(address, framing) => Box::new(TcpTransport::new(*address, *framing)),
+        Destination::Unix(path) => Box::new(UnixTransport::new(path.clone())?),
+    })
+}
+
+struct UdpTransport {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.socket.send_to(packet, self.target)?;
+        Ok(())
+    }
+}
+
+/// Connects on the first send, and reconnects with exponential backoff after the
+/// connection fails. Packets sent while disconnected are dropped.
+struct TcpTransport {
+    address: SocketAddr,
+    framing: Framing,
+    stream: Option<TcpStream>,
+    backoff: Backoff,
+    frame: Vec<u8>,
+}
+
+impl TcpTransport {
+    fn new(address: SocketAddr, framing: Framing) -> Self {
+        TcpTransport {
+            address,
+            framing,
+            stream: None,
+            backoff: Backoff::new(),
+            frame: Vec::with_capacity(1024),
+        }
+    }
+
+    fn connect(&mut self) -> io::Result<&mut TcpStream> {
+        if self.stream.is_none() {
+            if !self.backoff.is_due() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    format!("not connected to {}", self.address),
+                ));
+            }
+            let stream = TcpStream::connect_timeout(&self.address, CONNECT_TIMEOUT).and_then(|stream| {
+                stream.set_nodelay(true)?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Ok(stream)
+            });
+            match stream {
+                Ok(stream) => {
+                    eprintln!("Connected to {}", self.address);
+                    self.stream = Some(stream);
+                    self.backoff.succeed();
+                }
+                Err(e) => {
+                    self.disconnect();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.backoff.fail();
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.clear();
+        match self.framing {
+            Framing::Slip => slip_encode(packet, &mut frame),
+            Framing::LengthPrefix => {
+                frame.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+                frame.extend_from_slice(packet);
+            }
+        }
+        let result = self.connect().and_then(|stream| stream.write_all(&frame));
+        self.frame = frame;
+        if let Err(e) = result {
+            // after a partial write the stream is out of sync, start over
+            if self.stream.is_some() {
+                self.disconnect();
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+fn slip_encode(packet: &[u8], frame: &mut Vec<u8>) {
+    frame.push(SLIP_END);
+    for byte in packet {
+        match *byte {
+            SLIP_END => frame.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => frame.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            byte => frame.push(byte),
+        }
+    }
+    frame.push(SLIP_END);
+}
+
+#[cfg(unix)]
+struct UnixTransport {
+    socket: std::os::unix::net::UnixDatagram,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    fn new(path: PathBuf) -> io::Result<Self> {
+        Ok(UnixTransport {
+            socket: std::os::unix::net::UnixDatagram::unbound()?,
+            path,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixTransport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.socket.send_to(packet, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+enum UnixTransport {}
+
+#[cfg(not(unix))]
+impl UnixTransport {
+    fn new(_path: PathBuf) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Other, "Unix domain sockets are not supported on this platform"))
+    }
+}
+
+#[cfg(not(unix))]
+impl Transport for UnixTransport {
+    fn send(&mut self, _packet: &[u8]) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tcp_framing() {
+        let mut frame = Vec::new();
+        slip_encode(&[1, SLIP_END, 2, SLIP_ESC], &mut frame);
+        assert_eq!(vec![SLIP_END, 1, SLIP_ESC, SLIP_ESC_END, 2, SLIP_ESC, SLIP_ESC_ESC, SLIP_END], frame);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let destination = Destination::Tcp(listener.local_addr().unwrap(), Framing::LengthPrefix);
+        let mut transport = open(&destination, &socket).unwrap();
+        transport.send(&[1, 2, 3, 4]).unwrap();
+        let mut received = [0; 8];
+        listener.accept().unwrap().0.read_exact(&mut received).unwrap();
+        assert_eq!([0, 0, 0, 4, 1, 2, 3, 4], received);
+    }
+}
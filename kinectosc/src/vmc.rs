@@ -0,0 +1,303 @@
+use std::io;
+use std::time::Instant;
+
+use k4a::joint_id;
+use nalgebra::{Matrix3, Point3, Quaternion, Rotation3, UnitQuaternion, Vector3};
+
+use super::filter::FilteredJoint;
+use super::joint_map::TrackerPose;
+use super::osc::{Argument, Bundle, OscMessage};
+
+/// Azure Kinect joint frames in the T-pose.
+#[derive(Clone, Copy)]
+enum Basis {
+    Spine,
+    LeftArm,
+    LeftHand,
+    RightArm,
+    RightHand,
+    LeftHip,
+    LeftFoot,
+    RightHip,
+    RightFoot,
+}
+
+impl Basis {
+    /// The frame after conversion to Unity as the Azure Kinect Unity body tracking sample
+    /// converts it, taken from that sample.
+    fn sample_rotation(self) -> UnitQuaternion<f64> {
+        let (x, y, z) = (Vector3::x(), Vector3::y(), Vector3::z());
+        match self {
+            Basis::Spine | Basis::LeftHip => look_rotation(x, -z),
+            Basis::LeftArm => look_rotation(y, -z),
+            Basis::LeftHand => look_rotation(-z, -y),
+            Basis::RightArm => look_rotation(-y, z),
+            Basis::RightHand => UnitQuaternion::identity(),
+            Basis::LeftFoot => look_rotation(x, y),
+            Basis::RightHip => look_rotation(x, z),
+            Basis::RightFoot => look_rotation(x, -y),
+        }
+    }
+
+    /// The orientation the tracker reports for the joint when the body faces the sensor
+    /// in the T-pose.
+    fn t_pose(self) -> UnitQuaternion<f64> {
+        // undo the sample's conversion, which turns around y after mirroring on x
+        let q = (y_180().inverse() * self.sample_rotation()).into_inner();
+        UnitQuaternion::new_unchecked(Quaternion::new(q.w, q.i, -q.j, -q.k))
+    }
+}
+
+struct Bone {
+    name: &'static str,
+    joint: joint_id::Type,
+    /// Index of the parent in `BONES`.
+    parent: Option<usize>,
+    basis: Basis,
+}
+
+const fn bone(name: &'static str, joint: joint_id::Type, parent: Option<usize>, basis: Basis) -> Bone {
+    Bone { name, joint, parent, basis }
+}
+
+/// Unity humanoid bones driven by the Azure Kinect joints, parents first. Hands, eyes and
+/// ears have no humanoid bone of their own.
+const BONES: [Bone; 21] = [
+    bone("Hips", joint_id::K4ABT_JOINT_PELVIS, None, Basis::Spine),
+    bone("Spine", joint_id::K4ABT_JOINT_SPINE_NAVEL, Some(0), Basis::Spine),
+    bone("Chest", joint_id::K4ABT_JOINT_SPINE_CHEST, Some(1), Basis::Spine),
+    bone("Neck", joint_id::K4ABT_JOINT_NECK, Some(2), Basis::Spine),
+    bone("Head", joint_id::K4ABT_JOINT_HEAD, Some(3), Basis::Spine),
+    bone("LeftShoulder", joint_id::K4ABT_JOINT_CLAVICLE_LEFT, Some(2), Basis::LeftArm),
+    bone("LeftUpperArm", joint_id::K4ABT_JOINT_SHOULDER_LEFT, Some(5), Basis::LeftArm),
+    bone("LeftLowerArm", joint_id::K4ABT_JOINT_ELBOW_LEFT, Some(6), Basis::LeftArm),
+    bone("LeftHand", joint_id::K4ABT_JOINT_WRIST_LEFT, Some(7), Basis::LeftHand),
+    bone("RightShoulder", joint_id::K4ABT_JOINT_CLAVICLE_RIGHT, Some(2), Basis::RightArm),
+    bone("RightUpperArm", joint_id::K4ABT_JOINT_SHOULDER_RIGHT, Some(9), Basis::RightArm),
+    bone("RightLowerArm", joint_id::K4ABT_JOINT_ELBOW_RIGHT, Some(10), Basis::RightArm),
+    bone("RightHand", joint_id::K4ABT_JOINT_WRIST_RIGHT, Some(11), Basis::RightHand),
+    bone("LeftUpperLeg", joint_id::K4ABT_JOINT_HIP_LEFT, Some(0), Basis::LeftHip),
+    bone("LeftLowerLeg", joint_id::K4ABT_JOINT_KNEE_LEFT, Some(13), Basis::LeftHip),
+    bone("LeftFoot", joint_id::K4ABT_JOINT_ANKLE_LEFT, Some(14), Basis::LeftFoot),
+    bone("LeftToes", joint_id::K4ABT_JOINT_FOOT_LEFT, Some(15), Basis::LeftFoot),
+    bone("RightUpperLeg", joint_id::K4ABT_JOINT_HIP_RIGHT, Some(0), Basis::RightHip),
+    bone("RightLowerLeg", joint_id::K4ABT_JOINT_KNEE_RIGHT, Some(17), Basis::RightHip),
+    bone("RightFoot", joint_id::K4ABT_JOINT_ANKLE_RIGHT, Some(18), Basis::RightFoot),
+    bone("RightToes", joint_id::K4ABT_JOINT_FOOT_RIGHT, Some(19), Basis::RightFoot),
+];
+
+// values of /VMC/Ext/OK
+const LOADED: i32 = 1;
+const CALIBRATED: i32 = 3;
+const NORMAL_CALIBRATION: i32 = 0;
+
+/// A pose in Unity coordinates.
+struct Pose {
+    position: Point3<f64>,
+    rotation: UnitQuaternion<f64>,
+}
+
+/// Converts a position in millimeters in the depth camera frame, which is right-handed
+/// with y down, to Unity coordinates.
+fn to_unity_position(position: &Point3<f64>) -> Point3<f64> {
+    // mirror y, then turn around y
+    Point3::new(-position.x, -position.y, -position.z) / 1000.0
+}
+
+/// Converts an orientation in the depth camera frame to Unity coordinates, the frame
+/// `to_unity_position` maps to. That is a reflection through the origin, which leaves
+/// every rotation as it is.
+fn to_unity_rotation(orientation: &UnitQuaternion<f64>) -> UnitQuaternion<f64> {
+    *orientation
+}
+
+fn y_180() -> UnitQuaternion<f64> {
+    UnitQuaternion::new_unchecked(Quaternion::new(0.0, 0.0, 1.0, 0.0))
+}
+
+/// Unity's `Quaternion.LookRotation`.
+fn look_rotation(forward: Vector3<f64>, up: Vector3<f64>) -> UnitQuaternion<f64> {
+    let right = up.cross(&forward);
+    UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(Matrix3::from_columns(&[
+        right, up, forward,
+    ])))
+}
+
+/// Local poses of the humanoid bones, in `BONES` order. The hips are relative to the root,
+/// every other bone to its parent.
+fn bone_poses(joints: &[FilteredJoint], recenter: &Vector3<f64>) -> Vec<Pose> {
+    let world: Vec<Pose> = BONES
+        .iter()
+        .map(|bone| {
+            let joint = &joints[bone.joint as usize];
+            Pose {
+                position: to_unity_position(&(joint.filtered_position + recenter)),
+                rotation: to_unity_rotation(&joint.filtered_orientation) * bone.basis.t_pose().inverse(),
+            }
+        })
+        .collect();
+    BONES
+        .iter()
+        .zip(&world)
+        .map(|(bone, pose)| match bone.parent {
+            Some(parent) => {
+                let parent = &world[parent];
+                let inverse = parent.rotation.inverse();
+                Pose {
+                    position: Point3::from(inverse * (pose.position - parent.position)),
+                    rotation: inverse * pose.rotation,
+                }
+            }
+            None => Pose {
+                position: pose.position,
+                rotation: pose.rotation,
+            },
+        })
+        .collect()
+}
+
+fn pose_message(address: &str, name: &str, pose: &Pose) -> OscMessage {
+    let p = &pose.position;
+    let q = pose.rotation.quaternion();
+    let mut arguments = vec![Argument::String(name.into())];
+    arguments.extend(
+        [p.x, p.y, p.z, q.i, q.j, q.k, q.w]
+            .iter()
+            .map(|value| Argument::Float(*value as f32)),
+    );
+    OscMessage {
+        address: address.into(),
+        arguments,
+    }
+}
+
+/// Encodes skeletons in the Virtual Motion Capture protocol, which drives a Unity
+/// humanoid avatar. Positions there are in meters in Unity's left-handed frame, with y up
+/// and the sensor at the origin, and bone rotations are local to the parent bone,
+/// relative to the T-pose.
+pub struct Encoder {
+    started_at: Instant,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder {
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Adds the messages of one frame to `bundle`, posing the avatar after `joints` and
+    /// sending `trackers` as virtual trackers with their id as serial. Without `joints`
+    /// only the tracking status is sent, so that receivers keep the last pose.
+    pub fn encode(
+        &self,
+        bundle: &mut Bundle,
+        joints: Option<&[FilteredJoint]>,
+        trackers: &[(u32, TrackerPose)],
+        recenter: &Vector3<f64>,
+    ) -> io::Result<()> {
+        if let Some(joints) = joints {
+            let root = Pose {
+                position: Point3::origin(),
+                rotation: UnitQuaternion::identity(),
+            };
+            bundle.push(pose_message("/VMC/Ext/Root/Pos", "root", &root))?;
+            for (bone, pose) in BONES.iter().zip(bone_poses(joints, recenter)) {
+                bundle.push(pose_message("/VMC/Ext/Bone/Pos", bone.name, &pose))?;
+            }
+            for (id, tracker) in trackers {
+                let pose = Pose {
+                    position: to_unity_position(&(tracker.position + recenter)),
+                    rotation: to_unity_rotation(&tracker.orientation),
+                };
+                bundle.push(pose_message("/VMC/Ext/Tra/Pos", &id.to_string(), &pose))?;
+            }
+        }
+        bundle.push(OscMessage {
+            address: "/VMC/Ext/OK".into(),
+            arguments: vec![
+                Argument::Int(LOADED),
+                Argument::Int(CALIBRATED),
+                Argument::Int(NORMAL_CALIBRATION),
+                Argument::Int(joints.is_some() as i32),
+            ],
+        })?;
+        bundle.push(OscMessage {
+            address: "/VMC/Ext/T".into(),
+            arguments: vec![Argument::Float(self.started_at.elapsed().as_secs_f32())],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Joints in the T-pose, facing the sensor 2 m away, as the tracker reports them.
+    fn t_pose() -> Vec<FilteredJoint> {
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        let orientations = [
+            (Basis::Spine, [0.5, 0.5, 0.5, 0.5]),
+            (Basis::LeftArm, [0., 0., h, h]),
+            (Basis::LeftHand, [0., 0., 0., 1.]),
+            (Basis::RightArm, [0., 0., h, -h]),
+            (Basis::RightHand, [0., 0., 1., 0.]),
+            (Basis::LeftHip, [0.5, 0.5, 0.5, 0.5]),
+            (Basis::LeftFoot, [h, 0., h, 0.]),
+            (Basis::RightHip, [0.5, -0.5, 0.5, -0.5]),
+            (Basis::RightFoot, [0., h, 0., h]),
+        ];
+        let mut joints = vec![FilteredJoint::default(); joint_id::K4ABT_JOINT_COUNT as usize];
+        for bone in BONES.iter() {
+            let (_, [w, i, j, k]) = orientations.iter().find(|(basis, _)| *basis as u8 == bone.basis as u8).unwrap();
+            let joint = &mut joints[bone.joint as usize];
+            joint.filtered_orientation = UnitQuaternion::new_normalize(Quaternion::new(*w, *i, *j, *k));
+            // spread out, so that every bone has a length
+            joint.filtered_position = Point3::new(bone.joint as f64 * 10.0, bone.joint as f64 * -20.0, 2000.0);
+        }
+        joints[joint_id::K4ABT_JOINT_PELVIS as usize].filtered_position = Point3::new(100.0, -200.0, 2000.0);
+        joints[joint_id::K4ABT_JOINT_SPINE_NAVEL as usize].filtered_position = Point3::new(100.0, -400.0, 2000.0);
+        joints[joint_id::K4ABT_JOINT_SHOULDER_LEFT as usize].filtered_position = Point3::new(200.0, -700.0, 2000.0);
+        joints[joint_id::K4ABT_JOINT_ELBOW_LEFT as usize].filtered_position = Point3::new(450.0, -700.0, 2000.0);
+        joints
+    }
+
+    #[test]
+    fn test_t_pose() {
+        let poses = bone_poses(&t_pose(), &Vector3::new(0.0, 0.0, -1000.0));
+        for (bone, pose) in BONES.iter().zip(&poses) {
+            assert!(pose.rotation.angle() < 1e-9, "{} is rotated", bone.name);
+        }
+        assert!((poses[0].position - Point3::new(-0.1, 0.2, -1.0)).norm() < 1e-9);
+        // the spine is above the hips
+        assert!((poses[1].position - Point3::new(0.0, 0.2, 0.0)).norm() < 1e-9);
+        // the left arm points to the avatar's left, which faces +z
+        assert!((poses[7].position - Point3::new(-0.25, 0.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_positions_and_rotations_share_a_frame() {
+        let turn = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.5)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.2);
+        let v = Point3::new(300.0, -500.0, 700.0);
+        assert!((to_unity_position(&(turn * v)) - to_unity_rotation(&turn) * to_unity_position(&v)).norm() < 1e-9);
+
+        // turning the whole body only turns the hips
+        let joints = t_pose();
+        let turned: Vec<FilteredJoint> = joints
+            .iter()
+            .map(|joint| FilteredJoint {
+                filtered_position: turn * joint.filtered_position,
+                filtered_orientation: turn * joint.filtered_orientation,
+                ..joint.clone()
+            })
+            .collect();
+        let poses = bone_poses(&joints, &Vector3::zeros());
+        let turned_poses = bone_poses(&turned, &Vector3::zeros());
+        assert!(turned_poses[0].rotation.angle_to(&to_unity_rotation(&turn)) < 1e-9);
+        for (pose, turned_pose) in poses.iter().zip(&turned_poses).skip(1) {
+            assert!((pose.position - turned_pose.position).norm() < 1e-9);
+            assert!(pose.rotation.angle_to(&turned_pose.rotation) < 1e-9);
+        }
+    }
+}
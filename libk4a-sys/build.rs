@@ -1,8 +1,15 @@
+#[cfg(feature = "sdk")]
 extern crate bindgen;
 
+#[cfg(feature = "sdk")]
 use std::env;
+#[cfg(feature = "sdk")]
 use std::path::PathBuf;
 
+#[cfg(not(feature = "sdk"))]
+fn main() {}
+
+#[cfg(feature = "sdk")]
 fn main() {
     if cfg!(target_os = "windows") {
         println!("cargo:rustc-link-search=C:\\Program Files\\Azure Kinect SDK v1.4.1\\sdk\\windows-desktop\\amd64\\release\\lib");
@@ -19,9 +26,9 @@ fn main() {
         .prepend_enum_name(false)
         .derive_copy(false)
         .no_copy("k4a_body_t|k4a_skelton_t|k4a_joint_t")
-        .newtype_enum("k4a_result_t|k4a_buffer_result_t|k4a_calibration_model_type_t|k4a_calibration_type_t|k4a_color_control_command_t|k4a_color_control_mode_t|k4a_firmware_build_t|k4a_firmware_signature_t|k4a_log_level_t|k4a_playback_seek_origin_t|k4a_stream_result_t|k4a_transformation_interpolation_type_t|k4a_wait_result_t|k4a_image_format_t|k4a_color_resolution_t|k4a_depth_mode_t|k4a_fps_t|k4a_wired_sync_mode_t|k4abt_sensor_orientation_t|k4abt_tracker_processing_mode_t|k4abt_joint_confidence_level_t")
-        .constified_enum_module("k4abt_joint_id_t")
-        .blacklist_type("k4a_float2_t|k4a_float2_t__xy|k4a_float3_t|k4a_float3_t__xyz|k4a_quaternion_t|k4a_quaternion_t__wxyz")
+        .newtype_enum("k4a_result_t|k4a_buffer_result_t|k4a_calibration_model_type_t|k4a_calibration_type_t|k4a_color_control_command_t|k4a_color_control_mode_t|k4a_firmware_build_t|k4a_firmware_signature_t|k4a_log_level_t|k4a_playback_seek_origin_t|k4a_stream_result_t|k4a_transformation_interpolation_type_t|k4a_wait_result_t|k4a_image_format_t|k4a_color_resolution_t|k4a_depth_mode_t|k4a_fps_t|k4a_wired_sync_mode_t|k4abt_sensor_orientation_t|k4abt_tracker_processing_mode_t")
+        .blacklist_type("k4a_float2_t|k4a_float2_t__xy|k4a_float3_t|k4a_float3_t__xyz|k4a_quaternion_t|k4a_quaternion_t__wxyz|k4abt_joint_confidence_level_t|k4abt_joint_id_t|k4abt_joint_t|k4abt_skeleton_t|k4abt_body_t")
+        .blacklist_item("K4ABT_BODY_INDEX_MAP_BACKGROUND")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks));
 
     if cfg!(target_os = "windows") {
@@ -2,8 +2,10 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+#[cfg(feature = "sdk")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(feature = "sdk")]
 impl Default for k4a_device_configuration_t {
     fn default() -> Self {
         k4a_device_configuration_t {
@@ -20,12 +22,31 @@ impl Default for k4a_device_configuration_t {
     }
 }
 
+#[cfg(feature = "sdk")]
+impl Clone for k4a_device_configuration_t {
+    fn clone(&self) -> Self {
+        k4a_device_configuration_t {
+            color_format: self.color_format,
+            color_resolution: self.color_resolution,
+            depth_mode: self.depth_mode,
+            camera_fps: self.camera_fps,
+            synchronized_images_only: self.synchronized_images_only,
+            depth_delay_off_color_usec: self.depth_delay_off_color_usec,
+            wired_sync_mode: self.wired_sync_mode,
+            subordinate_delay_off_master_usec: self.subordinate_delay_off_master_usec,
+            disable_streaming_indicator: self.disable_streaming_indicator,
+        }
+    }
+}
+
+#[cfg(feature = "sdk")]
 impl Default for k4abt_tracker_configuration_t {
     fn default() -> Self {
         k4abt_tracker_configuration_t {
             sensor_orientation: k4abt_sensor_orientation_t::K4ABT_SENSOR_ORIENTATION_DEFAULT,
             processing_mode: k4abt_tracker_processing_mode_t::K4ABT_TRACKER_PROCESSING_MODE_GPU,
             gpu_device_id: 0,
+            model_path: std::ptr::null(),
         }
     }
 }
@@ -53,3 +74,78 @@ pub struct k4a_quaternion_t {
     pub y: f32,
     pub z: f32,
 }
+
+// The body tracking results are written by hand like the vector types above, so that
+// they can be used without the SDKs.
+
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct k4abt_joint_confidence_level_t(pub u32);
+
+impl k4abt_joint_confidence_level_t {
+    pub const K4ABT_JOINT_CONFIDENCE_NONE: k4abt_joint_confidence_level_t = k4abt_joint_confidence_level_t(0);
+    pub const K4ABT_JOINT_CONFIDENCE_LOW: k4abt_joint_confidence_level_t = k4abt_joint_confidence_level_t(1);
+    pub const K4ABT_JOINT_CONFIDENCE_MEDIUM: k4abt_joint_confidence_level_t = k4abt_joint_confidence_level_t(2);
+    pub const K4ABT_JOINT_CONFIDENCE_HIGH: k4abt_joint_confidence_level_t = k4abt_joint_confidence_level_t(3);
+    pub const K4ABT_JOINT_CONFIDENCE_LEVELS_COUNT: k4abt_joint_confidence_level_t = k4abt_joint_confidence_level_t(4);
+}
+
+pub mod k4abt_joint_id_t {
+    pub type Type = u32;
+    pub const K4ABT_JOINT_PELVIS: Type = 0;
+    pub const K4ABT_JOINT_SPINE_NAVEL: Type = 1;
+    pub const K4ABT_JOINT_SPINE_CHEST: Type = 2;
+    pub const K4ABT_JOINT_NECK: Type = 3;
+    pub const K4ABT_JOINT_CLAVICLE_LEFT: Type = 4;
+    pub const K4ABT_JOINT_SHOULDER_LEFT: Type = 5;
+    pub const K4ABT_JOINT_ELBOW_LEFT: Type = 6;
+    pub const K4ABT_JOINT_WRIST_LEFT: Type = 7;
+    pub const K4ABT_JOINT_HAND_LEFT: Type = 8;
+    pub const K4ABT_JOINT_HANDTIP_LEFT: Type = 9;
+    pub const K4ABT_JOINT_THUMB_LEFT: Type = 10;
+    pub const K4ABT_JOINT_CLAVICLE_RIGHT: Type = 11;
+    pub const K4ABT_JOINT_SHOULDER_RIGHT: Type = 12;
+    pub const K4ABT_JOINT_ELBOW_RIGHT: Type = 13;
+    pub const K4ABT_JOINT_WRIST_RIGHT: Type = 14;
+    pub const K4ABT_JOINT_HAND_RIGHT: Type = 15;
+    pub const K4ABT_JOINT_HANDTIP_RIGHT: Type = 16;
+    pub const K4ABT_JOINT_THUMB_RIGHT: Type = 17;
+    pub const K4ABT_JOINT_HIP_LEFT: Type = 18;
+    pub const K4ABT_JOINT_KNEE_LEFT: Type = 19;
+    pub const K4ABT_JOINT_ANKLE_LEFT: Type = 20;
+    pub const K4ABT_JOINT_FOOT_LEFT: Type = 21;
+    pub const K4ABT_JOINT_HIP_RIGHT: Type = 22;
+    pub const K4ABT_JOINT_KNEE_RIGHT: Type = 23;
+    pub const K4ABT_JOINT_ANKLE_RIGHT: Type = 24;
+    pub const K4ABT_JOINT_FOOT_RIGHT: Type = 25;
+    pub const K4ABT_JOINT_HEAD: Type = 26;
+    pub const K4ABT_JOINT_NOSE: Type = 27;
+    pub const K4ABT_JOINT_EYE_LEFT: Type = 28;
+    pub const K4ABT_JOINT_EAR_LEFT: Type = 29;
+    pub const K4ABT_JOINT_EYE_RIGHT: Type = 30;
+    pub const K4ABT_JOINT_EAR_RIGHT: Type = 31;
+    pub const K4ABT_JOINT_COUNT: Type = 32;
+}
+
+pub const K4ABT_BODY_INDEX_MAP_BACKGROUND: u32 = 255;
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct k4abt_joint_t {
+    pub position: k4a_float3_t,
+    pub orientation: k4a_quaternion_t,
+    pub confidence_level: k4abt_joint_confidence_level_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct k4abt_skeleton_t {
+    pub joints: [k4abt_joint_t; k4abt_joint_id_t::K4ABT_JOINT_COUNT as usize],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct k4abt_body_t {
+    pub id: u32,
+    pub skeleton: k4abt_skeleton_t,
+}
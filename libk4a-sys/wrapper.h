@@ -1,3 +1,4 @@
 #include <k4a/k4a.h>
 #include <k4arecord/playback.h>
+#include <k4arecord/record.h>
 #include <k4abt.h>